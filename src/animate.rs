@@ -0,0 +1,121 @@
+//! Interpolation between frames, for `--animate`.
+
+use crate::{
+  error::TileError,
+  xcb_util::geometry::*,
+};
+
+/// How the animation's progress changes over time.
+#[derive(Clone, Copy, Debug)]
+pub enum Easing {
+  Linear,
+  /// Fast to start with, slowing down towards the end.
+  EaseOut,
+  /// Slow at both ends.
+  EaseInOut,
+}
+
+impl std::str::FromStr for Easing {
+  type Err = TileError;
+  fn from_str(s: &str) -> Result<Easing, TileError> {
+    match s {
+      "linear" => Ok(Easing::Linear),
+      "ease-out" => Ok(Easing::EaseOut),
+      "ease-in-out" => Ok(Easing::EaseInOut),
+      _ => {
+        Err(TileError::ParseError {
+          arg: s.to_owned(),
+          expected: "one of linear, ease-out or ease-in-out",
+        })
+      }
+    }
+  }
+}
+
+impl Easing {
+  /// How far along the animation should be, from 0 to 1, after `t` of its
+  /// time, also from 0 to 1.
+  pub fn progress(self, t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    match self {
+      Easing::Linear => t,
+      Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+      Easing::EaseInOut => {
+        if t < 0.5 {
+          4.0 * t.powi(3)
+        } else {
+          1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+        }
+      }
+    }
+  }
+}
+
+/// The frame `progress` of the way from `from` to `to`.
+pub fn interpolate(from: ScreenRect, to: ScreenRect, progress: f32) -> ScreenRect {
+  let lerp = |a: i32, b: i32| a + ((b - a) as f32 * progress).round() as i32;
+  ScreenRect::new(
+    ScreenPoint::new(
+      lerp(from.origin.x, to.origin.x),
+      lerp(from.origin.y, to.origin.y),
+    ),
+    ScreenSize::new(
+      lerp(from.size.width, to.size.width),
+      lerp(from.size.height, to.size.height),
+    ),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const EASINGS: [Easing; 3] = [Easing::Linear, Easing::EaseOut, Easing::EaseInOut];
+
+  #[test]
+  fn every_easing_starts_at_0_and_ends_at_1() {
+    for easing in &EASINGS {
+      assert_eq!(easing.progress(0.0), 0.0, "{:?}", easing);
+      assert_eq!(easing.progress(1.0), 1.0, "{:?}", easing);
+      assert_eq!(easing.progress(-0.5), 0.0, "{:?}", easing);
+      assert_eq!(easing.progress(1.5), 1.0, "{:?}", easing);
+    }
+  }
+
+  #[test]
+  fn every_easing_only_goes_forwards() {
+    for easing in &EASINGS {
+      let progress = (0..=100)
+        .map(|t| easing.progress(t as f32 / 100.0))
+        .collect::<Vec<_>>();
+      assert!(
+        progress.windows(2).all(|p| p[0] <= p[1]),
+        "{:?}: {:?}",
+        easing,
+        progress
+      );
+    }
+  }
+
+  #[test]
+  fn interpolate_reaches_both_ends() {
+    let from = rect(0, 0, 800, 600);
+    let to = rect(1920, 28, 960, 1052);
+    assert_eq!(interpolate(from, to, 0.0), from);
+    assert_eq!(interpolate(from, to, 1.0), to);
+  }
+
+  #[test]
+  fn interpolate_rounds_to_the_nearest_pixel() {
+    // Halfway between 0 and 5 rounds to 3 going up and to 2 going down, so
+    // ties always go towards where the window is heading.
+    assert_eq!(
+      interpolate(rect(0, 0, 5, 5), rect(5, 5, 0, 0), 0.5),
+      rect(3, 3, 2, 2)
+    );
+    assert_eq!(
+      interpolate(rect(0, 0, 100, 100), rect(10, 10, 110, 110), 0.33),
+      rect(3, 3, 103, 103)
+    );
+  }
+}
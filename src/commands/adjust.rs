@@ -0,0 +1,233 @@
+use anyhow::Error;
+use log::debug;
+use structopt::StructOpt;
+
+use crate::{
+  commands::Fract,
+  context::Context,
+  error::TileError,
+  layout::{
+    overlap,
+    Direction,
+  },
+  snapshot::WindowInfo,
+  xcb_util::geometry::*,
+};
+
+/// A distance, either in pixels or as a fraction of the output's usable area.
+pub enum Step {
+  Pixels(i32),
+  Fraction(Fract),
+}
+
+impl std::str::FromStr for Step {
+  type Err = TileError;
+  fn from_str(s: &str) -> Result<Step, TileError> {
+    if s.contains('/') {
+      return Ok(Step::Fraction(Fract::from_str(s)?));
+    }
+    i32::from_str(s).map(Step::Pixels).map_err(|_| {
+      TileError::ParseError {
+        arg: s.to_owned(),
+        expected: "a number of pixels or a fraction like 1/10",
+      }
+    })
+  }
+}
+
+impl Step {
+  pub fn pixels(&self, length: i32) -> i32 {
+    match self {
+      Step::Pixels(pixels) => *pixels,
+      Step::Fraction(fract) => (fract.value() * length as f32) as i32,
+    }
+  }
+}
+
+#[derive(StructOpt)]
+pub struct Adjustment {
+  direction: Direction,
+
+  /// Pixels, or a fraction of the output's usable area like 1/10
+  #[structopt(default_value = "1/20")]
+  amount: Step,
+}
+
+/// Moves the active window by a step
+#[derive(StructOpt)]
+pub struct Nudge {
+  #[structopt(flatten)]
+  adjustment: Adjustment,
+}
+
+/// Moves one edge of the active window outwards by a step
+#[derive(StructOpt)]
+pub struct Grow {
+  #[structopt(flatten)]
+  adjustment: Adjustment,
+}
+
+/// Moves one edge of the active window inwards by a step; `shrink l` moves
+/// the right edge left
+#[derive(StructOpt)]
+pub struct Shrink {
+  #[structopt(flatten)]
+  adjustment: Adjustment,
+}
+
+enum Kind {
+  Nudge,
+  Grow,
+  Shrink,
+}
+
+/// Moves `edge` up to `distance` pixels towards `direction`, stopping at the
+/// first of `stops` on the way and never passing `limit`.
+fn step_edge(
+  edge: i32,
+  distance: i32,
+  direction: Direction,
+  stops: &[i32],
+  limit: Option<i32>,
+) -> i32 {
+  let sign = direction.sign();
+  let ahead = |a: i32, b: i32| (a - b) * sign > 0;
+
+  let mut target = edge + sign * distance;
+  if let Some(limit) = limit {
+    if !ahead(edge, limit) && ahead(target, limit) {
+      target = limit;
+    }
+  }
+  stops
+    .iter()
+    .copied()
+    .filter(|stop| ahead(*stop, edge) && !ahead(*stop, target))
+    .min_by_key(|stop| (stop - edge).abs())
+    .unwrap_or(target)
+}
+
+/// The edges an edge of `frame` moving along `direction` can stop at: the
+/// sides of the usable area, and the sides of other visible windows alongside
+/// `frame`.
+fn stops(
+  ctx: &Context,
+  window: &WindowInfo,
+  frame: ScreenRect,
+  usable: ScreenRect,
+  direction: Direction,
+) -> Vec<i32> {
+  let span = direction.cross_span(frame);
+  let mut stops = vec![
+    direction.leading_edge(usable),
+    direction.opposite().leading_edge(usable),
+  ];
+  for other in ctx.snapshot.visible_windows() {
+    let other_frame = other.frame.outer_rect(other.insets);
+    if other.id == window.id || overlap(direction.cross_span(other_frame), span) <= 0 {
+      continue;
+    }
+    stops.push(direction.leading_edge(other_frame));
+    stops.push(direction.opposite().leading_edge(other_frame));
+  }
+  stops
+}
+
+impl Adjustment {
+  fn run(&self, ctx: &Context, kind: Kind) -> Result<(), Error> {
+    let window = ctx.snapshot.active()?;
+
+    let output = ctx.snapshot.output_of(window)?;
+
+    let direction = self.direction;
+    let frame = window.frame.outer_rect(window.insets);
+    let distance = self.amount.pixels(direction.length(output.usable.size));
+    let limit = direction.leading_edge(output.usable);
+
+    let new_frame = match kind {
+      Kind::Nudge => {
+        let stops = stops(ctx, window, frame, output.usable, direction);
+        let edge = direction.leading_edge(frame);
+        let moved = step_edge(edge, distance, direction, &stops, Some(limit));
+        frame.translate(direction.offset((moved - edge) * direction.sign()))
+      }
+      Kind::Grow => {
+        let stops = stops(ctx, window, frame, output.usable, direction);
+        let edge = direction.leading_edge(frame);
+        let moved = step_edge(edge, distance, direction, &stops, Some(limit));
+        direction.with_leading_edge(frame, moved)
+      }
+      Kind::Shrink => {
+        // The edge facing `direction` moves back the other way, but no further
+        // than the window's minimum size allows.
+        let back = direction.opposite();
+        let min_size = window
+          .hints
+          .min_size
+          .unwrap_or_else(|| ScreenSize::new(1, 1))
+          .max(ScreenSize::new(1, 1));
+        let min_length = direction.length(min_size) + direction.length(frame.size)
+          - direction.length(window.frame.size);
+        let floor = back.leading_edge(frame) + direction.sign() * min_length;
+
+        let stops = stops(ctx, window, frame, output.usable, back);
+        let edge = direction.leading_edge(frame);
+        let moved = step_edge(edge, distance, back, &stops, Some(floor));
+        direction.with_leading_edge(frame, moved)
+      }
+    };
+
+    let new_frame = new_frame.inner_rect(window.insets);
+
+    debug!(
+      window = window.id,
+      output = output.name.as_str(),
+      distance = distance,
+      frame:% = window.frame,
+      new_frame:% = new_frame;
+      "Adjusting window"
+    );
+
+    ctx.move_resize(window, output, new_frame)
+  }
+}
+
+impl Nudge {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> { self.adjustment.run(ctx, Kind::Nudge) }
+}
+
+impl Grow {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> { self.adjustment.run(ctx, Kind::Grow) }
+}
+
+impl Shrink {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> { self.adjustment.run(ctx, Kind::Shrink) }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn step_edge_goes_the_whole_distance_without_stops() {
+    assert_eq!(step_edge(100, 50, Direction::East, &[], None), 150);
+    assert_eq!(step_edge(100, 50, Direction::North, &[], None), 50);
+  }
+
+  #[test]
+  fn step_edge_stops_at_the_first_stop_on_the_way() {
+    let stops = [90, 120, 130, 300];
+    assert_eq!(step_edge(100, 50, Direction::East, &stops, None), 120);
+    assert_eq!(step_edge(100, 50, Direction::West, &stops, None), 90);
+    // A stop right at the edge it starts from is already behind it.
+    assert_eq!(step_edge(120, 50, Direction::East, &stops, None), 130);
+  }
+
+  #[test]
+  fn step_edge_never_passes_the_limit() {
+    assert_eq!(step_edge(100, 50, Direction::East, &[], Some(130)), 130);
+    assert_eq!(step_edge(100, 50, Direction::West, &[60], Some(80)), 80);
+    // An edge already past the limit isn't pulled back to it.
+    assert_eq!(step_edge(200, 50, Direction::East, &[], Some(130)), 250);
+  }
+}
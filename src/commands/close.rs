@@ -0,0 +1,39 @@
+use anyhow::Error;
+use structopt::StructOpt;
+
+use crate::{
+  context::Context,
+  snapshot::WindowTarget,
+  xcb_util::window::WindowExt,
+};
+
+/// Closes a window: an X window id such as 0x3a00007, or by default the active
+/// window
+#[derive(StructOpt)]
+pub struct Close {
+  window: Option<WindowTarget>,
+
+  /// Kill the window's process instead of asking it to close, for windows
+  /// that have stopped responding
+  #[structopt(long)]
+  force: bool,
+}
+
+impl Close {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let root = ctx.snapshot.root;
+    let window = ctx
+      .snapshot
+      .find(self.window.unwrap_or(WindowTarget::Active))?;
+
+    if self.force {
+      ctx.request(format_args!("kill {:#x}", window.id), |conn| {
+        window.id.kill(conn)
+      })
+    } else {
+      ctx.request(format_args!("close {:#x}", window.id), |conn| {
+        root.close(conn, window.id)
+      })
+    }
+  }
+}
@@ -0,0 +1,89 @@
+use anyhow::Error;
+use structopt::StructOpt;
+
+use crate::{
+  context::Context,
+  error::TileError,
+  snapshot::DesktopTarget,
+  xcb_util::window::{
+    WindowExt,
+    ALL_DESKTOPS,
+  },
+};
+
+/// Switches to another desktop: a number counting from 0, next or prev
+#[derive(StructOpt)]
+pub struct SwitchDesktop {
+  desktop: DesktopTarget,
+}
+
+impl SwitchDesktop {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let root = ctx.snapshot.root;
+    let desktop = ctx.snapshot.desktop(self.desktop)?;
+    ctx.request(format_args!("switch to desktop {}", desktop), |conn| {
+      root.set_current_desktop(conn, desktop)
+    })
+  }
+}
+
+/// Moves the active window to another desktop: a number counting from 0, next
+/// or prev
+#[derive(StructOpt)]
+pub struct MoveToDesktop {
+  desktop: DesktopTarget,
+
+  /// Switch to the desktop as well, keeping the window focused
+  #[structopt(long)]
+  follow: bool,
+}
+
+impl MoveToDesktop {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let root = ctx.snapshot.root;
+    let window = ctx.snapshot.active()?;
+    let desktop = ctx.snapshot.desktop(self.desktop)?;
+
+    ctx.request(
+      format_args!("move {:#x} to desktop {}", window.id, desktop),
+      |conn| root.set_desktop(conn, window.id, desktop),
+    )?;
+    if self.follow {
+      ctx.request(format_args!("switch to desktop {}", desktop), |conn| {
+        root.set_current_desktop(conn, desktop)
+      })?;
+      ctx.request(format_args!("activate {:#x}", window.id), |conn| {
+        root.activate(conn, window.id)
+      })?;
+    }
+    Ok(())
+  }
+}
+
+/// Toggles whether the active window is shown on every desktop
+#[derive(StructOpt)]
+pub struct Pin {}
+
+impl Pin {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let root = ctx.snapshot.root;
+    let window = ctx.snapshot.active()?;
+
+    // Unpinned windows go back to whichever desktop is showing.
+    let (desktop, description) = if window.desktop == Some(ALL_DESKTOPS) {
+      let current = ctx
+        .snapshot
+        .current_desktop
+        .ok_or(TileError::WmUnsupported {
+          atom: "_NET_CURRENT_DESKTOP",
+        })?;
+      (current, "unpin")
+    } else {
+      (ALL_DESKTOPS, "pin")
+    };
+
+    ctx.request(format_args!("{} {:#x}", description, window.id), |conn| {
+      root.set_desktop(conn, window.id, desktop)
+    })
+  }
+}
@@ -0,0 +1,166 @@
+use anyhow::Error;
+use log::debug;
+use structopt::StructOpt;
+
+use crate::{
+  context::Context,
+  layout::{
+    overlap,
+    Direction,
+  },
+  xcb_util::geometry::*,
+};
+
+/// Grows the active window into the empty space around it, up to the nearest
+/// other windows and the edges of the output. With a direction, only that
+/// edge moves
+#[derive(StructOpt)]
+pub struct Fill {
+  direction: Option<Direction>,
+}
+
+/// `frame` with its side facing `direction` moved as far as it can go without
+/// running into `others` or leaving `usable`.
+fn fill_towards(
+  frame: ScreenRect,
+  others: &[ScreenRect],
+  usable: ScreenRect,
+  direction: Direction,
+) -> ScreenRect {
+  let sign = direction.sign();
+  let edge = direction.leading_edge(frame);
+  let span = direction.cross_span(frame);
+
+  let limit = others
+    .iter()
+    .filter(|other| overlap(direction.cross_span(**other), span) > 0)
+    .map(|other| direction.opposite().leading_edge(*other))
+    // Windows that the frame already overlaps don't stop it.
+    .filter(|near| (near - edge) * sign >= 0)
+    .chain(Some(direction.leading_edge(usable)))
+    .min_by_key(|near| (near - edge) * sign)
+    .unwrap_or(edge);
+
+  // Never shrink, even if the window already sticks out past the usable area.
+  if (limit - edge) * sign > 0 {
+    direction.with_leading_edge(frame, limit)
+  } else {
+    frame
+  }
+}
+
+impl Fill {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let window = ctx.snapshot.active()?;
+
+    let output = ctx.snapshot.output_of(window)?;
+
+    let others = ctx
+      .snapshot
+      .visible_windows()
+      .filter(|other| other.id != window.id)
+      .map(|other| other.frame.outer_rect(other.insets))
+      .collect::<Vec<_>>();
+    let frame = window.frame.outer_rect(window.insets);
+    let fill = |frame, directions: &[Direction]| {
+      directions.iter().fold(frame, |frame, direction| {
+        fill_towards(frame, &others, output.usable, *direction)
+      })
+    };
+
+    let new_frame = match self.direction {
+      Some(direction) => fill(frame, &[direction]),
+      None => {
+        // Growing sideways first can block growing vertically, and vice
+        // versa, so try both and keep whichever covers more.
+        let horizontal_first = fill(
+          frame,
+          &[
+            Direction::West,
+            Direction::East,
+            Direction::North,
+            Direction::South,
+          ],
+        );
+        let vertical_first = fill(
+          frame,
+          &[
+            Direction::North,
+            Direction::South,
+            Direction::West,
+            Direction::East,
+          ],
+        );
+        if vertical_first.area() > horizontal_first.area() {
+          vertical_first
+        } else {
+          horizontal_first
+        }
+      }
+    };
+    let new_frame = new_frame.inner_rect(window.insets);
+
+    debug!(
+      window = window.id,
+      output = output.name.as_str(),
+      frame:% = window.frame,
+      new_frame:% = new_frame;
+      "Filling empty space"
+    );
+
+    ctx.move_resize(window, output, new_frame)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn fills_to_the_usable_area_when_nothing_is_in_the_way() {
+    let usable = rect(0, 28, 1920, 1052);
+    let frame = rect(500, 300, 400, 300);
+    assert_eq!(
+      fill_towards(frame, &[], usable, Direction::West),
+      rect(0, 300, 900, 300)
+    );
+    assert_eq!(
+      fill_towards(frame, &[], usable, Direction::North),
+      rect(500, 28, 400, 572)
+    );
+  }
+
+  #[test]
+  fn stops_at_the_nearest_window_alongside() {
+    let usable = rect(0, 0, 1920, 1080);
+    let frame = rect(500, 300, 400, 300);
+    let others = [
+      rect(1500, 300, 300, 300),
+      rect(1200, 500, 100, 400),
+      // Not alongside, so it doesn't get in the way.
+      rect(1000, 700, 100, 100),
+    ];
+    assert_eq!(
+      fill_towards(frame, &others, usable, Direction::East),
+      rect(500, 300, 700, 300)
+    );
+  }
+
+  #[test]
+  fn windows_already_overlapped_dont_stop_it() {
+    let usable = rect(0, 0, 1920, 1080);
+    let frame = rect(500, 300, 400, 300);
+    let others = [rect(800, 200, 300, 200)];
+    assert_eq!(
+      fill_towards(frame, &others, usable, Direction::East),
+      rect(500, 300, 1420, 300)
+    );
+  }
+
+  #[test]
+  fn never_shrinks() {
+    let usable = rect(0, 0, 1920, 1080);
+    let frame = rect(1800, 300, 400, 300);
+    assert_eq!(fill_towards(frame, &[], usable, Direction::East), frame);
+  }
+}
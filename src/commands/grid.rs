@@ -0,0 +1,229 @@
+use std::cmp::{
+  max,
+  min,
+};
+
+use anyhow::Error;
+use log::debug;
+use structopt::StructOpt;
+
+use crate::{
+  commands::placement::place_on_output,
+  context::Context,
+  error::TileError,
+  layout::Direction,
+  xcb_util::{
+    geometry::*,
+    keyboard::{
+      Input,
+      KeyboardGrab,
+    },
+    overlay::{
+      Outline,
+      Rects,
+      GRID_LINE,
+    },
+  },
+};
+
+/// How wide grid lines are, in pixels.
+const LINE_WIDTH: i32 = 2;
+
+/// A column and row in the grid, counting from the top left.
+type Cell = (i32, i32);
+
+/// Shows a grid over the active window's output and moves the window to the
+/// cells picked. Drag across cells with the mouse, or move between cells with
+/// hjkl or the arrow keys, press space to start a selection at the current
+/// cell, and Enter to finish it. Escape leaves the window where it was. The
+/// grid is drawn as lines, so the windows below stay visible
+#[derive(StructOpt)]
+pub struct Grid {
+  #[structopt(long, default_value = "6")]
+  columns: i32,
+
+  #[structopt(long, default_value = "4")]
+  rows: i32,
+}
+
+impl Grid {
+  /// The cell under `point`, treating points outside `usable` as being in the
+  /// nearest cell.
+  fn cell_at(&self, point: ScreenPoint, usable: ScreenRect) -> Cell {
+    let offset = point - usable.origin;
+    (
+      (offset.x * self.columns / usable.size.width.max(1)).clamp(0, self.columns - 1),
+      (offset.y * self.rows / usable.size.height.max(1)).clamp(0, self.rows - 1),
+    )
+  }
+
+  fn step(&self, cell: Cell, direction: Direction) -> Cell {
+    let offset = direction.offset(1);
+    (
+      (cell.0 + offset.x).clamp(0, self.columns - 1),
+      (cell.1 + offset.y).clamp(0, self.rows - 1),
+    )
+  }
+
+  /// The part of the output covered by the cells from `a` to `b`.
+  fn selection(&self, a: Cell, b: Cell) -> DisplayPercentageSpaceRect {
+    let (columns, rows) = (self.columns as f32, self.rows as f32);
+    DisplayPercentageSpaceRect::new(
+      DisplayPercentageSpacePoint::new(min(a.0, b.0) as f32 / columns, min(a.1, b.1) as f32 / rows),
+      DisplayPercentageSpaceSize::new(
+        (max(a.0, b.0) - min(a.0, b.0) + 1) as f32 / columns,
+        (max(a.1, b.1) - min(a.1, b.1) + 1) as f32 / rows,
+      ),
+    )
+  }
+
+  fn lines(&self, usable: ScreenRect) -> Vec<ScreenRect> {
+    let mut lines = vec![];
+    for column in 0..=self.columns {
+      let x = usable.min_x() + usable.size.width * column / self.columns;
+      lines.push(ScreenRect::new(
+        ScreenPoint::new(x.min(usable.max_x() - LINE_WIDTH), usable.min_y()),
+        ScreenSize::new(LINE_WIDTH, usable.size.height),
+      ));
+    }
+    for row in 0..=self.rows {
+      let y = usable.min_y() + usable.size.height * row / self.rows;
+      lines.push(ScreenRect::new(
+        ScreenPoint::new(usable.min_x(), y.min(usable.max_y() - LINE_WIDTH)),
+        ScreenSize::new(usable.size.width, LINE_WIDTH),
+      ));
+    }
+    lines
+  }
+
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    if self.columns < 1 || self.rows < 1 {
+      return Err(
+        TileError::ParseError {
+          arg: format!("{}x{}", self.columns, self.rows),
+          expected: "at least one column and one row",
+        }
+        .into(),
+      );
+    }
+    let conn = ctx.require_conn("grid")?;
+    let root = ctx.snapshot.root;
+    let window = ctx.snapshot.active()?;
+    let output = ctx.snapshot.output_of(window)?;
+    let usable = output.usable;
+
+    let lines = Rects::show(conn, root, &self.lines(usable), GRID_LINE);
+    let outline = Outline::new(conn, root);
+    let mut input = KeyboardGrab::new(conn, root)?;
+    input.grab_pointer(root)?;
+
+    let mut cursor = self.cell_at(window.frame.center(), usable);
+    let mut anchor = None;
+    let mut dragging = false;
+    loop {
+      let selection = self.selection(anchor.unwrap_or(cursor), cursor);
+      outline.show(selection.to_rect(usable));
+
+      match input.next_input()? {
+        Input::Key(key) if key.is_cancel() => {
+          debug!(window = window.id; "Grid cancelled");
+          return Ok(());
+        }
+        Input::Key(key) if key.is_confirm() => break,
+        Input::Key(key) if key.char() == Some(' ') => {
+          anchor = match anchor {
+            Some(_) => None,
+            None => Some(cursor),
+          };
+        }
+        Input::Key(key) => {
+          if let Some(direction) = key.direction() {
+            cursor = self.step(cursor, direction);
+          }
+        }
+        Input::Press(point) if usable.contains(point) => {
+          cursor = self.cell_at(point, usable);
+          anchor = Some(cursor);
+          dragging = true;
+        }
+        Input::Motion(point) if dragging => cursor = self.cell_at(point, usable),
+        Input::Release(point) if dragging => {
+          cursor = self.cell_at(point, usable);
+          break;
+        }
+        _ => {}
+      }
+    }
+
+    drop(input);
+    drop(outline);
+    drop(lines);
+    let selection = self.selection(anchor.unwrap_or(cursor), cursor);
+    debug!(
+      window = window.id,
+      output = output.name.as_str(),
+      selection:? = selection;
+      "Picked grid cells"
+    );
+    place_on_output(ctx, window, output, selection, false)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const GRID: Grid = Grid {
+    columns: 6,
+    rows: 4,
+  };
+
+  #[test]
+  fn cell_at_finds_the_cell_under_a_point() {
+    let usable = rect(1920, 28, 1920, 1052);
+    assert_eq!(GRID.cell_at(ScreenPoint::new(1920, 28), usable), (0, 0));
+    assert_eq!(
+      GRID.cell_at(ScreenPoint::new(1920 + 320, 28 + 263), usable),
+      (1, 1)
+    );
+    assert_eq!(
+      GRID.cell_at(ScreenPoint::new(1920 + 319, 28 + 262), usable),
+      (0, 0)
+    );
+  }
+
+  #[test]
+  fn cell_at_the_far_edges_is_the_last_cell() {
+    let usable = rect(1920, 28, 1920, 1052);
+    assert_eq!(GRID.cell_at(ScreenPoint::new(3839, 1079), usable), (5, 3));
+    assert_eq!(GRID.cell_at(ScreenPoint::new(3840, 1080), usable), (5, 3));
+  }
+
+  #[test]
+  fn cell_at_clamps_points_off_the_output() {
+    let usable = rect(1920, 28, 1920, 1052);
+    assert_eq!(GRID.cell_at(ScreenPoint::new(0, 0), usable), (0, 0));
+    assert_eq!(GRID.cell_at(ScreenPoint::new(5000, 500), usable), (5, 1));
+  }
+
+  #[test]
+  fn selection_covers_both_cells_in_either_order() {
+    let expected = DisplayPercentageSpaceRect::new(
+      DisplayPercentageSpacePoint::new(1.0 / 6.0, 0.25),
+      DisplayPercentageSpaceSize::new(0.5, 0.5),
+    );
+    assert_eq!(GRID.selection((1, 1), (3, 2)), expected);
+    assert_eq!(GRID.selection((3, 2), (1, 1)), expected);
+    assert_eq!(GRID.selection((3, 1), (1, 2)), expected);
+  }
+
+  #[test]
+  fn selection_of_the_last_cell_reaches_the_corner() {
+    let last = GRID.selection((5, 3), (5, 3));
+    assert_eq!(last.max_x(), 1.0);
+    assert_eq!(last.max_y(), 1.0);
+    let all = GRID.selection((0, 0), (5, 3));
+    assert_eq!(all.origin, DisplayPercentageSpacePoint::new(0.0, 0.0));
+    assert_eq!(all.size, DisplayPercentageSpaceSize::new(1.0, 1.0));
+  }
+}
@@ -0,0 +1,150 @@
+use anyhow::Error;
+use log::debug;
+use structopt::StructOpt;
+
+use crate::{
+  commands::adjust::Step,
+  context::Context,
+  layout::Direction,
+  xcb_util::{
+    geometry::*,
+    keyboard::{
+      keysym,
+      KeyboardGrab,
+    },
+    overlay::Outline,
+  },
+};
+
+/// Moves and resizes the active window with the keyboard. An outline shows
+/// where the window will go: hjkl or the arrow keys move it, shift-hjkl moves
+/// its right or bottom edge, and a number typed first repeats the next step.
+/// Enter moves the window there, and Escape leaves it where it was
+#[derive(StructOpt)]
+pub struct Interactive {
+  /// How far each key moves the outline: pixels, or a fraction of the output's
+  /// usable area like 1/10
+  #[structopt(long, default_value = "1/20")]
+  step: Step,
+}
+
+/// `frame` with its right or bottom edge moved `distance` pixels towards
+/// `direction`, keeping it at least `min_size`.
+fn resize(
+  frame: ScreenRect,
+  direction: Direction,
+  distance: i32,
+  min_size: ScreenSize,
+) -> ScreenRect {
+  let edge = match direction {
+    Direction::East | Direction::West => Direction::East,
+    Direction::North | Direction::South => Direction::South,
+  };
+  let floor = edge.opposite().leading_edge(frame) + edge.length(min_size);
+  let moved = edge.leading_edge(frame) + direction.sign() * distance;
+  edge.with_leading_edge(frame, moved.max(floor))
+}
+
+impl Interactive {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let conn = ctx.require_conn("interactive")?;
+    let root = ctx.snapshot.root;
+    let window = ctx.snapshot.active()?;
+    let layout = &ctx.snapshot.layout;
+    let mut output = ctx.snapshot.output_of(window)?;
+
+    let min_size = window
+      .hints
+      .min_size
+      .unwrap_or_else(|| ScreenSize::new(1, 1))
+      .max(ScreenSize::new(1, 1))
+      + (window.frame.outer_rect(window.insets).size - window.frame.size);
+
+    let mut frame = window.frame.outer_rect(window.insets);
+    let outline = Outline::new(conn, root);
+    outline.show(frame);
+    let keyboard = KeyboardGrab::new(conn, root)?;
+
+    let mut count = 0;
+    loop {
+      let key = keyboard.next_key()?;
+      if key.is_cancel() {
+        debug!(window = window.id; "Interactive move cancelled");
+        return Ok(());
+      }
+      if key.is_confirm() {
+        break;
+      }
+      if let Some(digit) = key.char().and_then(|c| c.to_digit(10)) {
+        count = (count * 10 + digit as i32).min(1000);
+        continue;
+      }
+      if key.keysym == keysym::BACKSPACE {
+        count /= 10;
+        continue;
+      }
+
+      if let Some(direction) = key.direction() {
+        let distance = self
+          .step
+          .pixels(direction.length(output.usable.size))
+          .max(1)
+          * count.max(1);
+        frame = if key.shift {
+          resize(frame, direction, distance, min_size)
+        } else {
+          frame.translate(direction.offset(distance))
+        };
+        output = layout.output_for(frame).unwrap_or(output);
+        debug!(frame:% = frame, output = output.name.as_str(); "Moved outline");
+        outline.show(frame);
+      }
+      count = 0;
+    }
+
+    drop(keyboard);
+    drop(outline);
+    ctx.move_resize(window, output, frame.inner_rect(window.insets))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const MIN_SIZE: ScreenSize = ScreenSize::new(100, 50);
+
+  #[test]
+  fn resize_moves_the_right_or_bottom_edge() {
+    let frame = rect(100, 100, 400, 300);
+    assert_eq!(
+      resize(frame, Direction::East, 40, MIN_SIZE),
+      rect(100, 100, 440, 300)
+    );
+    assert_eq!(
+      resize(frame, Direction::West, 40, MIN_SIZE),
+      rect(100, 100, 360, 300)
+    );
+    assert_eq!(
+      resize(frame, Direction::South, 40, MIN_SIZE),
+      rect(100, 100, 400, 340)
+    );
+    assert_eq!(
+      resize(frame, Direction::North, 40, MIN_SIZE),
+      rect(100, 100, 400, 260)
+    );
+  }
+
+  #[test]
+  fn resize_stops_at_the_minimum_size() {
+    let frame = rect(100, 100, 400, 300);
+    assert_eq!(
+      resize(frame, Direction::West, 1000, MIN_SIZE),
+      rect(100, 100, 100, 300)
+    );
+    assert_eq!(
+      resize(frame, Direction::North, 1000, MIN_SIZE),
+      rect(100, 100, 400, 50)
+    );
+  }
+}
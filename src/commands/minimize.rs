@@ -0,0 +1,90 @@
+use anyhow::Error;
+use structopt::StructOpt;
+
+use crate::{
+  context::Context,
+  error::TileError,
+  snapshot::WindowTarget,
+  xcb_util::window::WindowExt,
+};
+
+/// Minimises the active window
+#[derive(StructOpt)]
+pub struct Minimize {}
+
+impl Minimize {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let root = ctx.snapshot.root;
+    let window = ctx.snapshot.active()?;
+    ctx.request(format_args!("minimize {:#x}", window.id), |conn| {
+      root.iconify(conn, window.id)
+    })
+  }
+}
+
+/// Restores a minimised window and focuses it: an X window id such as
+/// 0x3a00007, or by default the topmost minimised window on this desktop
+#[derive(StructOpt)]
+pub struct Restore {
+  window: Option<WindowTarget>,
+}
+
+impl Restore {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let root = ctx.snapshot.root;
+    let window = match self.window {
+      Some(target) => ctx.snapshot.find(target)?,
+      None => {
+        ctx
+          .snapshot
+          .minimised_windows()
+          .next()
+          .ok_or(TileError::NoMinimisedWindow)?
+      }
+    };
+    ctx.request(format_args!("activate {:#x}", window.id), |conn| {
+      root.activate(conn, window.id)
+    })
+  }
+}
+
+/// Minimises every other window on the active window's output
+#[derive(StructOpt)]
+pub struct MinimizeOthers {}
+
+impl MinimizeOthers {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let root = ctx.snapshot.root;
+    let window = ctx.snapshot.active()?;
+    let layout = &ctx.snapshot.layout;
+    let output = ctx.snapshot.output_of(window)?;
+
+    for other in ctx.snapshot.visible_windows() {
+      let on_output = layout
+        .output_for(other.frame)
+        .is_some_and(|o| o.name == output.name);
+      if other.id != window.id && on_output {
+        ctx.request(format_args!("minimize {:#x}", other.id), |conn| {
+          root.iconify(conn, other.id)
+        })?;
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Toggles the window manager's "show desktop" mode, which hides every window
+#[derive(StructOpt)]
+pub struct ShowDesktop {}
+
+impl ShowDesktop {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let root = ctx.snapshot.root;
+    // WMs that haven't entered the mode yet may not have set the property.
+    let showing = !ctx.snapshot.showing_desktop.unwrap_or(false);
+    let description = if showing { "show" } else { "hide" };
+    ctx.request(format_args!("{} desktop", description), |conn| {
+      root.set_showing_desktop(conn, showing)
+    })
+  }
+}
@@ -0,0 +1,93 @@
+//! The subcommands. Each one works out what to do from the context's
+//! snapshot, and makes any changes through the context so that dry runs and
+//! replays work.
+
+use std::str;
+
+use structopt::StructOpt;
+
+use crate::error::TileError;
+
+mod adjust;
+mod close;
+mod desktop;
+mod fill;
+mod grid;
+mod interactive;
+mod minimize;
+mod pick;
+mod placement;
+mod query;
+mod stacking;
+
+pub use adjust::{
+  Grow,
+  Nudge,
+  Shrink,
+};
+pub use close::Close;
+pub use desktop::{
+  MoveToDesktop,
+  Pin,
+  SwitchDesktop,
+};
+pub use fill::Fill;
+pub use grid::Grid;
+pub use interactive::Interactive;
+pub use minimize::{
+  Minimize,
+  MinimizeOthers,
+  Restore,
+  ShowDesktop,
+};
+pub use pick::Pick;
+pub use placement::{
+  MoveWindowOnOutput,
+  MoveWindowToOutput,
+};
+pub use query::{
+  Query,
+  SaveSnapshot,
+};
+pub use stacking::{
+  AlwaysBelow,
+  AlwaysOnTop,
+  Lower,
+  Raise,
+  Restack,
+};
+
+#[derive(StructOpt)]
+pub struct Fract {
+  num: f32,
+  denom: f32,
+}
+
+impl Fract {
+  pub fn value(&self) -> f32 { self.num / self.denom }
+}
+
+impl std::str::FromStr for Fract {
+  type Err = TileError;
+  fn from_str(s: &str) -> Result<Fract, TileError> {
+    let err = || {
+      TileError::ParseError {
+        arg: s.to_owned(),
+        expected: "a fraction like 1/2",
+      }
+    };
+    let mut parts = s.split('/');
+    let (num, denom) = match (parts.next(), parts.next(), parts.next()) {
+      (Some(num), Some(denom), None) => (num, denom),
+      _ => return Err(err()),
+    };
+    let fract = Fract {
+      num: f32::from_str(num).map_err(|_| err())?,
+      denom: f32::from_str(denom).map_err(|_| err())?,
+    };
+    if fract.denom == 0.0 {
+      return Err(err());
+    }
+    Ok(fract)
+  }
+}
@@ -0,0 +1,244 @@
+use anyhow::Error;
+use log::debug;
+use structopt::StructOpt;
+use xcb::base as xbase;
+
+use crate::{
+  context::Context,
+  error::TileError,
+  snapshot::WindowInfo,
+  xcb_util::{
+    geometry::*,
+    keyboard::{
+      Input,
+      KeyboardGrab,
+    },
+    overlay::Labels,
+    window::WindowExt,
+  },
+};
+
+/// The keys hint labels are made from, home row first.
+const HINT_KEYS: &str = "asdfghjkl";
+
+/// What to do with a picked window.
+#[derive(Clone, Copy)]
+pub enum PickAction {
+  Focus,
+  Swap,
+  Move,
+  Close,
+}
+
+impl std::str::FromStr for PickAction {
+  type Err = TileError;
+  fn from_str(s: &str) -> Result<PickAction, TileError> {
+    match s {
+      "focus" => Ok(PickAction::Focus),
+      "swap" => Ok(PickAction::Swap),
+      "move" => Ok(PickAction::Move),
+      "close" => Ok(PickAction::Close),
+      _ => {
+        Err(TileError::ParseError {
+          arg: s.to_owned(),
+          expected: "one of focus, swap, move or close",
+        })
+      }
+    }
+  }
+}
+
+/// Picks a window by clicking on it, and then focuses it, swaps it with the
+/// active window, moves it to the active window's output or closes it
+#[derive(StructOpt)]
+pub struct Pick {
+  action: PickAction,
+
+  /// Label every visible window other than panels, and pick one by typing its
+  /// label instead of clicking
+  #[structopt(long)]
+  hints: bool,
+}
+
+/// `count` distinct labels of one key each if there are few enough windows,
+/// or otherwise of two keys.
+fn hint_labels(count: usize) -> Vec<String> {
+  let keys = HINT_KEYS.chars().collect::<Vec<_>>();
+  if count <= keys.len() {
+    return keys.iter().take(count).map(|k| k.to_string()).collect();
+  }
+  keys
+    .iter()
+    .flat_map(|a| keys.iter().map(move |b| format!("{}{}", a, b)))
+    .take(count)
+    .collect()
+}
+
+/// Shows a label on each window and waits until one is typed.
+fn pick_by_hint<'s>(
+  ctx: &'s Context,
+  conn: &xbase::Connection,
+  input: &KeyboardGrab,
+) -> Result<Option<&'s WindowInfo>, Error> {
+  let mut windows = ctx.snapshot.visible_windows().collect::<Vec<_>>();
+  windows.sort_by_key(|w| (w.frame.origin.y, w.frame.origin.x));
+  let labels = hint_labels(windows.len());
+  let hints = windows.into_iter().zip(labels).collect::<Vec<_>>();
+
+  let shown = hints
+    .iter()
+    .map(|(window, label)| (window.frame.center(), label.clone()))
+    .collect::<Vec<_>>();
+  let _labels = Labels::show(conn, ctx.snapshot.root, &shown)?;
+
+  let mut typed = String::new();
+  loop {
+    let key = input.next_key()?;
+    if key.is_cancel() {
+      return Ok(None);
+    }
+    typed.extend(key.char());
+    let matching = hints
+      .iter()
+      .filter(|(_, label)| label.starts_with(&typed))
+      .collect::<Vec<_>>();
+    match matching.as_slice() {
+      [] => {
+        debug!(typed = typed.as_str(); "No window has that label");
+        return Ok(None);
+      }
+      [(window, label)] if *label == typed => return Ok(Some(window)),
+      _ => {}
+    }
+  }
+}
+
+/// Waits for a click, and finds the topmost window under it. A click on a
+/// panel or on the desktop picks nothing, rather than the window behind it.
+fn pick_by_click<'s>(
+  ctx: &'s Context,
+  input: &KeyboardGrab,
+) -> Result<Option<&'s WindowInfo>, Error> {
+  loop {
+    match input.next_input()? {
+      Input::Key(key) if key.is_cancel() => return Ok(None),
+      Input::Press(point) => {
+        let snapshot = &ctx.snapshot;
+        let hit = snapshot
+          .stacking
+          .iter()
+          .rev()
+          .filter_map(|id| snapshot.window(*id))
+          .filter(|w| snapshot.on_current_desktop(w) && !w.is_minimised())
+          .find(|w| w.frame.outer_rect(w.insets).contains(point));
+        return Ok(hit.filter(|w| !w.is_dock_or_desktop()));
+      }
+      _ => {}
+    }
+  }
+}
+
+impl Pick {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let conn = ctx.require_conn("pick")?;
+    let root = ctx.snapshot.root;
+
+    let mut input = KeyboardGrab::new(conn, root)?;
+    let picked = if self.hints {
+      pick_by_hint(ctx, conn, &input)?
+    } else {
+      input.grab_pointer(root)?;
+      pick_by_click(ctx, &input)?
+    };
+    drop(input);
+
+    let picked = match picked {
+      Some(picked) => picked,
+      None => {
+        debug!("No window picked");
+        return Ok(());
+      }
+    };
+    debug!(window = picked.id; "Picked window");
+
+    match self.action {
+      PickAction::Focus => {
+        ctx.request(format_args!("activate {:#x}", picked.id), |conn| {
+          root.activate(conn, picked.id)
+        })
+      }
+      PickAction::Close => {
+        ctx.request(format_args!("close {:#x}", picked.id), |conn| {
+          root.close(conn, picked.id)
+        })
+      }
+      PickAction::Swap => swap(ctx, ctx.snapshot.active()?, picked),
+      PickAction::Move => move_to_active_output(ctx, picked),
+    }
+  }
+}
+
+/// Puts each of `a` and `b` where the other one is.
+fn swap(ctx: &Context, a: &WindowInfo, b: &WindowInfo) -> Result<(), Error> {
+  if a.id == b.id {
+    return Ok(());
+  }
+  let a_output = ctx.snapshot.output_of(a)?;
+  let b_output = ctx.snapshot.output_of(b)?;
+  let a_frame = a.frame.outer_rect(a.insets);
+  let b_frame = b.frame.outer_rect(b.insets);
+
+  // Move the second window even if the first didn't end up quite where it
+  // was asked to, so a fussy WM doesn't leave them stacked on each other.
+  let first = ctx.move_resize(a, b_output, b_frame.inner_rect(a.insets));
+  let second = ctx.move_resize(b, a_output, a_frame.inner_rect(b.insets));
+  first.and(second)
+}
+
+/// Moves `window` to the active window's output, keeping its position and
+/// size relative to the output.
+fn move_to_active_output(ctx: &Context, window: &WindowInfo) -> Result<(), Error> {
+  let active = ctx.snapshot.active()?;
+  let to = ctx.snapshot.output_of(active)?;
+  let from = ctx.snapshot.output_of(window)?;
+
+  let frame = window
+    .frame
+    .outer_rect(window.insets)
+    .as_dps(from.usable)
+    .to_rect(to.usable)
+    .inner_rect(window.insets);
+  ctx.move_resize(window, to, frame)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn few_windows_get_one_key_each() {
+    assert!(hint_labels(0).is_empty());
+    assert_eq!(hint_labels(3), ["a", "s", "d"]);
+    assert_eq!(hint_labels(HINT_KEYS.len()).concat(), HINT_KEYS);
+  }
+
+  #[test]
+  fn more_windows_than_keys_get_two_keys_each() {
+    let labels = hint_labels(HINT_KEYS.len() + 1);
+    assert_eq!(labels.len(), HINT_KEYS.len() + 1);
+    assert_eq!(labels[..3], ["aa", "as", "ad"]);
+    // With every label the same length, none is a prefix of another, so
+    // typing one never stops early at a shorter label.
+    assert!(labels.iter().all(|l| l.len() == 2));
+    let mut unique = labels.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), labels.len());
+  }
+
+  #[test]
+  fn labels_run_out_after_every_pair_of_keys() {
+    let pairs = HINT_KEYS.len() * HINT_KEYS.len();
+    assert_eq!(hint_labels(pairs + 10).len(), pairs);
+  }
+}
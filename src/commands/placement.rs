@@ -0,0 +1,170 @@
+use anyhow::Error;
+use log::debug;
+use structopt::StructOpt;
+
+use crate::{
+  commands::Fract,
+  context::Context,
+  error::TileError,
+  layout::{
+    Direction,
+    Output,
+  },
+  snapshot::WindowInfo,
+  xcb_util::geometry::*,
+};
+
+#[derive(StructOpt)]
+pub struct MoveWindowOnOutput {
+  x: Fract,
+  y: Fract,
+  w: Fract,
+  h: Fract,
+
+  /// Show where the window will go first, and only move it if Enter is
+  /// pressed. Works with --dry-run, to try out placements without moving
+  /// anything
+  #[structopt(long)]
+  preview: bool,
+}
+
+impl MoveWindowOnOutput {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let window = ctx.snapshot.active()?;
+
+    let output = ctx.snapshot.output_of(window)?;
+
+    let pct = DisplayPercentageSpaceRect::new(
+      DisplayPercentageSpacePoint::new(self.x.value(), self.y.value()),
+      DisplayPercentageSpaceSize::new(self.w.value(), self.h.value()),
+    );
+
+    place_on_output(ctx, window, output, pct, self.preview)
+  }
+}
+
+/// Moves `window` to `pct` of `output`'s usable area, with its decorations
+/// inside that area. With `preview`, the user gets to see where first.
+pub fn place_on_output(
+  ctx: &Context,
+  window: &WindowInfo,
+  output: &Output,
+  pct: DisplayPercentageSpaceRect,
+  preview: bool,
+) -> Result<(), Error> {
+  let new_rect = pct.to_rect(output.usable).inner_rect(window.insets);
+
+  debug!(
+    window = window.id,
+    output = output.name.as_str(),
+    usable:% = output.usable,
+    frame:% = new_rect;
+    "Placing window on output"
+  );
+
+  if preview && !ctx.preview(window, output, new_rect)? {
+    return Ok(());
+  }
+
+  // NOTE: Some window managers (Kwin and XFWM, for example) may refuse to
+  // position windows as requested if they are in a "tiled" or "maximised"
+  // state. In the case of Kwin, this can be fixed by using a window rule to
+  // force the "ignore requested geometry" flag to `false`.
+  ctx.move_resize(window, output, new_rect)
+}
+
+/// What to keep the same when a window moves to another output.
+pub enum Preserve {
+  /// Position and size, relative to the output.
+  Proportional,
+  /// Size and offset from the output's top left corner, in pixels.
+  Size,
+  /// Size in pixels, and where the center is relative to the output.
+  Position,
+}
+
+impl std::str::FromStr for Preserve {
+  type Err = TileError;
+  fn from_str(s: &str) -> Result<Preserve, TileError> {
+    match s {
+      "proportional" => Ok(Preserve::Proportional),
+      "size" => Ok(Preserve::Size),
+      "position" => Ok(Preserve::Position),
+      _ => {
+        Err(TileError::ParseError {
+          arg: s.to_owned(),
+          expected: "one of proportional, size or position",
+        })
+      }
+    }
+  }
+}
+
+#[derive(StructOpt)]
+pub struct MoveWindowToOutput {
+  direction: Direction,
+
+  /// What to keep the same: proportional (position and size relative to the
+  /// output), size (pixel size and offset from the output's corner) or position
+  /// (pixel size, and the center's position relative to the output). Windows
+  /// that don't fit are shrunk
+  #[structopt(long, default_value = "proportional")]
+  preserve: Preserve,
+
+  /// From the last output in that direction, go round to the first
+  #[structopt(long)]
+  wrap: bool,
+}
+
+impl MoveWindowToOutput {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let window = ctx.snapshot.active()?;
+
+    let layout = &ctx.snapshot.layout;
+    let current_output = ctx.snapshot.output_of(window)?;
+
+    let new_output = layout
+      .neighbour(current_output, window.frame, self.direction)
+      .or_else(|| {
+        if self.wrap {
+          layout.far_end(current_output, window.frame, self.direction.opposite())
+        } else {
+          None
+        }
+      })
+      .ok_or(TileError::NoOutputInDirection)?;
+
+    let decorated_source_frame = window.frame.outer_rect(window.insets);
+    let (from, to) = (current_output.usable, new_output.usable);
+    let size = decorated_source_frame.size;
+
+    let decorated_dest_frame = match self.preserve {
+      Preserve::Proportional => decorated_source_frame.as_dps(from).to_rect(to),
+      Preserve::Size => {
+        let offset = decorated_source_frame.origin - from.origin;
+        ScreenRect::new(to.origin + offset, size).clamp_into(to)
+      }
+      Preserve::Position => {
+        let center = DisplayPercentageSpaceRect::new(
+          decorated_source_frame.as_dps(from).center(),
+          DisplayPercentageSpaceSize::zero(),
+        )
+        .to_rect(to)
+        .origin;
+        ScreenRect::new(center - size.to_vector() / 2, size).clamp_into(to)
+      }
+    };
+    let bare_dest_frame = decorated_dest_frame.inner_rect(window.insets);
+
+    debug!(
+      window = window.id,
+      from_output = current_output.name.as_str(),
+      to_output = new_output.name.as_str(),
+      frame:% = window.frame,
+      new_frame:% = bare_dest_frame;
+      "Moving window to output"
+    );
+
+    ctx.move_resize(window, new_output, bare_dest_frame)
+  }
+}
@@ -0,0 +1,72 @@
+use std::path::PathBuf;
+
+use anyhow::Error;
+use structopt::StructOpt;
+
+use crate::context::Context;
+
+/// Prints the outputs x11-tile sees and where they came from, to help work out
+/// why a window went somewhere unexpected.
+#[derive(StructOpt)]
+pub struct Query {}
+
+impl Query {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let snapshot = &ctx.snapshot;
+    println!(
+      "Screen {}: root {:#x}, {}",
+      snapshot.screen, snapshot.root, snapshot.root_frame
+    );
+    println!("Outputs from {}:", snapshot.layout.source);
+    for output in &snapshot.layout.outputs {
+      println!(
+        "  {}{}{}: {}, usable {}",
+        output.name,
+        if output.primary { " (primary)" } else { "" },
+        if output.configured {
+          " (configured)"
+        } else {
+          ""
+        },
+        output.frame,
+        output.usable
+      );
+    }
+    match snapshot.active() {
+      Ok(window) => {
+        println!(
+          "Active window: {:#x} at {} on {}",
+          window.id,
+          window.frame,
+          snapshot
+            .layout
+            .output_for(window.frame)
+            .map_or("no output", |o| o.name.as_str())
+        );
+      }
+      Err(_) => println!("Active window: none"),
+    }
+    Ok(())
+  }
+}
+
+/// Writes a snapshot of the display to a file (or stdout), for attaching to
+/// bug reports.
+#[derive(StructOpt)]
+pub struct SaveSnapshot {
+  #[structopt(parse(from_os_str))]
+  path: Option<PathBuf>,
+}
+
+impl SaveSnapshot {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    // Give each screen its own file, rather than overwriting the same one.
+    let path = match &self.path {
+      Some(path) if ctx.all_screens => {
+        Some(path.with_extension(format!("{}.json", ctx.snapshot.screen)))
+      }
+      path => path.clone(),
+    };
+    ctx.snapshot.save(path.as_deref())
+  }
+}
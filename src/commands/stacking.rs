@@ -0,0 +1,109 @@
+use anyhow::Error;
+use structopt::StructOpt;
+use xcb::xproto;
+
+use crate::{
+  context::Context,
+  snapshot::{
+    WindowInfo,
+    WindowTarget,
+  },
+  xcb_util::window::WindowExt,
+};
+
+/// Raises the active window above the others in its layer
+#[derive(StructOpt)]
+pub struct Raise {}
+
+impl Raise {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let root = ctx.snapshot.root;
+    let window = ctx.snapshot.active()?;
+    ctx.request(format_args!("raise {:#x}", window.id), |conn| {
+      root.restack(conn, window.id, None, xproto::STACK_MODE_ABOVE)
+    })
+  }
+}
+
+/// Lowers the active window below the others in its layer
+#[derive(StructOpt)]
+pub struct Lower {}
+
+impl Lower {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let root = ctx.snapshot.root;
+    let window = ctx.snapshot.active()?;
+    ctx.request(format_args!("lower {:#x}", window.id), |conn| {
+      root.restack(conn, window.id, None, xproto::STACK_MODE_BELOW)
+    })
+  }
+}
+
+/// Adds `state` to the window if it doesn't have it, or removes it if it does.
+fn toggle_state(
+  ctx: &Context,
+  window: &WindowInfo,
+  state: &'static str,
+  name: &str,
+) -> Result<(), Error> {
+  let root = ctx.snapshot.root;
+  // Working out the new state here, rather than asking the WM to toggle it,
+  // means dry runs can say what will happen.
+  let enabled = !window.state.iter().any(|s| s == state);
+  let description = if enabled { "set" } else { "clear" };
+  ctx.request(
+    format_args!("{} {} on {:#x}", description, name, window.id),
+    |conn| root.set_state(conn, window.id, state, enabled),
+  )
+}
+
+/// Toggles whether the active window is kept above other windows
+#[derive(StructOpt)]
+pub struct AlwaysOnTop {}
+
+impl AlwaysOnTop {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let window = ctx.snapshot.active()?;
+    toggle_state(ctx, window, "_NET_WM_STATE_ABOVE", "always-on-top")
+  }
+}
+
+/// Toggles whether the active window is kept below other windows
+#[derive(StructOpt)]
+pub struct AlwaysBelow {}
+
+impl AlwaysBelow {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let window = ctx.snapshot.active()?;
+    toggle_state(ctx, window, "_NET_WM_STATE_BELOW", "always-below")
+  }
+}
+
+/// Stacks windows in the given order, top first. The first window keeps its
+/// place, and each of the others goes directly below the one before it.
+/// Windows are X window ids, such as 0x3a00007, or active
+#[derive(StructOpt)]
+pub struct Restack {
+  #[structopt(required = true)]
+  windows: Vec<WindowTarget>,
+}
+
+impl Restack {
+  pub fn run(&self, ctx: &Context) -> Result<(), Error> {
+    let root = ctx.snapshot.root;
+    let windows = self
+      .windows
+      .iter()
+      .map(|target| ctx.snapshot.find(*target))
+      .collect::<Result<Vec<_>, _>>()?;
+
+    for pair in windows.windows(2) {
+      let (above, window) = (pair[0], pair[1]);
+      ctx.request(
+        format_args!("restack {:#x} below {:#x}", window.id, above.id),
+        |conn| root.restack(conn, window.id, Some(above.id), xproto::STACK_MODE_BELOW),
+      )?;
+    }
+    Ok(())
+  }
+}
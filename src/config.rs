@@ -0,0 +1,106 @@
+use std::{
+  env,
+  fs::File,
+  io,
+  path::{
+    Path,
+    PathBuf,
+  },
+};
+
+use anyhow::{
+  anyhow,
+  Error,
+};
+use log::debug;
+use serde::Deserialize;
+
+use crate::xcb_util::geometry::*;
+
+/// A region of the screen that placement commands treat as an output, like
+/// `xrandr --setmonitor` but without changing the display's configuration.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct VirtualMonitor {
+  pub name: String,
+  /// In root window coordinates.
+  pub x: i32,
+  pub y: i32,
+  pub width: i32,
+  pub height: i32,
+}
+
+impl VirtualMonitor {
+  pub fn frame(&self) -> ScreenRect {
+    ScreenRect::new(
+      ScreenPoint::new(self.x, self.y),
+      ScreenSize::new(self.width, self.height),
+    )
+  }
+}
+
+/// Divides an output into a grid of regions, each of which placement commands
+/// treat as an output of its own.
+///
+/// Columns and rows are given as relative sizes, so `[1, 2, 1]` is a quarter,
+/// a half and a quarter. Regions are named after the output and numbered from
+/// 1 left to right, then top to bottom (`DP-1:1`, `DP-1:2`, ...), unless
+/// `names` says otherwise.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Split {
+  pub output: String,
+  #[serde(default)]
+  pub columns: Vec<u32>,
+  #[serde(default)]
+  pub rows: Vec<u32>,
+  #[serde(default)]
+  pub names: Vec<String>,
+}
+
+/// The contents of `config.json`. Everything is optional.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+  /// Any output these overlap is replaced by them.
+  pub monitors: Vec<VirtualMonitor>,
+  /// Applied before `monitors`.
+  pub splits: Vec<Split>,
+}
+
+impl Config {
+  /// `$XDG_CONFIG_HOME/x11-tile/config.json`, or `~/.config/...` if that isn't
+  /// set.
+  fn default_path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+      .map(PathBuf::from)
+      .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(dir.join("x11-tile").join("config.json"))
+  }
+
+  /// Reads the config from `path`, or from the default location. It's fine for
+  /// there to be nothing at the default location, but not at `path`.
+  pub fn load(path: Option<&Path>) -> Result<Config, Error> {
+    let (path, required) = match path {
+      Some(path) => (path.to_owned(), true),
+      None => {
+        match Config::default_path() {
+          Some(path) => (path, false),
+          None => return Ok(Config::default()),
+        }
+      }
+    };
+
+    let file = match File::open(&path) {
+      Ok(file) => file,
+      Err(e) if e.kind() == io::ErrorKind::NotFound && !required => {
+        debug!("No config at {}", path.display());
+        return Ok(Config::default());
+      }
+      Err(e) => return Err(anyhow!("Couldn't open config {}: {}", path.display(), e)),
+    };
+    debug!("Reading config from {}", path.display());
+    serde_json::from_reader(io::BufReader::new(file))
+      .map_err(|e| anyhow!("Couldn't read config {}: {}", path.display(), e))
+  }
+}
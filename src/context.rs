@@ -0,0 +1,335 @@
+use std::{
+  fmt,
+  path::Path,
+  thread,
+  time::{
+    Duration,
+    Instant,
+  },
+};
+
+use anyhow::{
+  anyhow,
+  Error,
+};
+use log::debug;
+use xcb::base as xbase;
+
+use crate::{
+  animate::{
+    self,
+    Easing,
+  },
+  config::Config,
+  error::TileError,
+  layout::Output,
+  snap,
+  snapshot::{
+    Snapshot,
+    WindowInfo,
+  },
+  xcb_util::{
+    geometry::*,
+    keyboard::{
+      Input,
+      KeyboardGrab,
+    },
+    overlay::Outline,
+    window::{
+      SizeHints,
+      WindowExt,
+    },
+  },
+  GlobalOptions,
+  ScreenSelection,
+};
+
+/// How long each step of an animated move takes: about one frame at 60Hz.
+const ANIMATION_STEP: Duration = Duration::from_millis(16);
+
+/// How often `wait_for_frame` checks where the window is.
+const WAIT_INTERVAL: Duration = Duration::from_millis(25);
+
+/// How long `preview` waits for the user to confirm.
+const PREVIEW_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The state every command runs against.
+///
+/// When replaying a snapshot there's no connection, and requests are printed
+/// rather than sent, as they are for a dry run.
+///
+/// Each context covers a single X screen; with `--screen all` the command runs
+/// once per screen, each time with a different context.
+pub struct Context<'a> {
+  pub conn: Option<&'a xbase::Connection>,
+  pub snapshot: Snapshot,
+  pub dry_run: bool,
+  pub all_screens: bool,
+  /// The `--snap` threshold, in pixels.
+  pub snap: i32,
+  /// How long `--animate` takes; no time at all means moves aren't animated.
+  pub animate: Duration,
+  pub easing: Easing,
+  /// How long to wait for the WM to apply a move; no time at all means moves
+  /// aren't checked.
+  pub wait: Duration,
+}
+
+impl<'a> Context<'a> {
+  pub fn replay(path: &Path, options: &GlobalOptions) -> Result<Context<'a>, Error> {
+    let mut snapshot = Snapshot::load(path)?;
+    snapshot.set_usable_area(options.usable_area);
+    Ok(Context {
+      conn: None,
+      snapshot,
+      dry_run: true,
+      all_screens: false,
+      snap: options.snap,
+      animate: Duration::from_millis(options.animate),
+      easing: options.easing,
+      wait: Duration::from_millis(options.wait),
+    })
+  }
+
+  pub fn capture(
+    conn: &'a xbase::Connection,
+    screen: usize,
+    config: &Config,
+    options: &GlobalOptions,
+  ) -> Result<Context<'a>, Error> {
+    let mut snapshot = Snapshot::capture(conn, screen, config)?;
+    snapshot.set_usable_area(options.usable_area);
+    Ok(Context {
+      conn: Some(conn),
+      snapshot,
+      dry_run: options.dry_run,
+      all_screens: matches!(options.screen, Some(ScreenSelection::All)),
+      snap: options.snap,
+      animate: Duration::from_millis(options.animate),
+      easing: options.easing,
+      wait: Duration::from_millis(options.wait),
+    })
+  }
+
+  /// The X connection, for commands that wait for the user and so can't be
+  /// replayed.
+  pub fn require_conn(&self, command: &str) -> Result<&'a xbase::Connection, Error> {
+    self.conn.ok_or_else(|| {
+      anyhow!(
+        "The {} command needs an X server, so it can't be replayed",
+        command
+      )
+    })
+  }
+
+  /// Moves `window` to `frame`, after snapping it to nearby edges if `--snap`
+  /// was given.
+  pub fn move_resize(
+    &self,
+    window: &WindowInfo,
+    output: &Output,
+    frame: ScreenRect,
+  ) -> Result<(), Error> {
+    let frame = self.snap(window, output, frame);
+    match &self.conn {
+      Some(conn) if !self.dry_run => {
+        if self.animate > Duration::from_millis(0) {
+          self.animate(conn, window, frame)?;
+        }
+        self.snapshot.root.move_resize(conn, window.id, frame)?;
+        self.wait_for_frame(conn, window, frame)
+      }
+      _ => {
+        println!(
+          "move-resize {:#x} on {}: {} -> {}",
+          window.id, output.name, window.frame, frame
+        );
+        Ok(())
+      }
+    }
+  }
+
+  /// Shows where `move_resize` would put `window` as an outline, and returns
+  /// whether the user pressed Enter to go ahead. Any other key cancels, as
+  /// does waiting for `PREVIEW_TIMEOUT`. Replays print the frame and go ahead.
+  pub fn preview(
+    &self,
+    window: &WindowInfo,
+    output: &Output,
+    frame: ScreenRect,
+  ) -> Result<bool, Error> {
+    let frame = self.snap(window, output, frame);
+    // Unlike the other requests this one is fine in a dry run, since it
+    // doesn't change anything.
+    let conn = match self.conn {
+      Some(conn) => conn,
+      None => {
+        println!("preview {:#x} on {}: {}", window.id, output.name, frame);
+        return Ok(true);
+      }
+    };
+
+    let outline = Outline::new(conn, self.snapshot.root);
+    outline.show(frame.outer_rect(window.insets));
+    let input = KeyboardGrab::new(conn, self.snapshot.root)?;
+    let confirmed = matches!(
+      input.next_input_within(PREVIEW_TIMEOUT)?,
+      Some(Input::Key(key)) if key.is_confirm()
+    );
+    debug!(window = window.id, frame:% = frame, confirmed = confirmed; "Previewed move");
+    Ok(confirmed)
+  }
+
+  /// Runs `send`, which should make one change to the display, unless this is
+  /// a dry run, in which case `description` is printed instead.
+  pub fn request(
+    &self,
+    description: fmt::Arguments,
+    send: impl FnOnce(&xbase::Connection) -> Result<(), Error>,
+  ) -> Result<(), Error> {
+    match &self.conn {
+      Some(conn) if !self.dry_run => send(conn),
+      _ => {
+        println!("{}", description);
+        Ok(())
+      }
+    }
+  }
+
+  fn snap(&self, window: &WindowInfo, output: &Output, frame: ScreenRect) -> ScreenRect {
+    if self.snap <= 0 {
+      return frame;
+    }
+
+    let mut others = vec![output.usable];
+    others.extend(
+      self
+        .snapshot
+        .visible_windows()
+        .filter(|other| other.id != window.id)
+        .map(|other| other.frame.outer_rect(other.insets)),
+    );
+    let snapped = snap::snap(
+      frame.outer_rect(window.insets),
+      window.frame.outer_rect(window.insets),
+      &others,
+      self.snap,
+    )
+    .inner_rect(window.insets);
+
+    if snapped != frame {
+      debug!(window = window.id, frame:% = frame, snapped:% = snapped; "Snapped window");
+    }
+    snapped
+  }
+
+  /// Moves `window` most of the way to `frame` a step at a time, leaving the
+  /// last step to `move_resize`. Gives up early if the window falls more than
+  /// a step behind, since a WM or client that slow would only make the
+  /// animation jerky.
+  fn animate(
+    &self,
+    conn: &xbase::Connection,
+    window: &WindowInfo,
+    frame: ScreenRect,
+  ) -> Result<(), Error> {
+    let root = self.snapshot.root;
+    let start = Instant::now();
+    let steps = (self.animate.as_millis() / ANIMATION_STEP.as_millis()).max(1) as u32;
+    let mut previous = window.frame;
+    for step in 1..steps {
+      let t = step as f32 / steps as f32;
+      let next = animate::interpolate(window.frame, frame, self.easing.progress(t));
+      root.move_resize(conn, window.id, next)?;
+
+      let due = start + self.animate * step / steps;
+      if let Some(wait) = due.checked_duration_since(Instant::now()) {
+        thread::sleep(wait);
+      }
+      // The WM may lag a step behind, but no more.
+      let actual = window.id.get_frame(conn, root)?;
+      if !arrived(&window.hints, actual, next) && !arrived(&window.hints, actual, previous) {
+        debug!(
+          window = window.id,
+          step = step,
+          expected:% = next,
+          actual:% = actual;
+          "Window isn't keeping up; skipping the rest of the animation"
+        );
+        break;
+      }
+      previous = next;
+    }
+    Ok(())
+  }
+
+  /// Waits up to `--wait` for the WM to apply a move/resize, failing if it
+  /// doesn't. Sizes are allowed to differ from `frame` as far as the window's
+  /// size hints allow.
+  fn wait_for_frame(
+    &self,
+    conn: &xbase::Connection,
+    window: &WindowInfo,
+    frame: ScreenRect,
+  ) -> Result<(), Error> {
+    if self.wait == Duration::from_millis(0) {
+      return Ok(());
+    }
+
+    let deadline = Instant::now() + self.wait;
+    let mut actual;
+    loop {
+      let remaining = deadline.saturating_duration_since(Instant::now());
+      thread::sleep(remaining.min(WAIT_INTERVAL));
+      actual = window.id.get_frame(conn, self.snapshot.root)?;
+      if arrived(&window.hints, actual, frame) {
+        return Ok(());
+      }
+      if Instant::now() >= deadline {
+        break;
+      }
+    }
+
+    Err(
+      TileError::WindowRefusedGeometry {
+        window: window.id,
+        requested: frame,
+        actual,
+      }
+      .into(),
+    )
+  }
+}
+
+/// Whether a window with `hints` that's at `actual` got to `frame`, allowing
+/// for the size hints rounding its size.
+fn arrived(hints: &SizeHints, actual: ScreenRect, frame: ScreenRect) -> bool {
+  actual.origin == frame.origin
+    && (actual.size == frame.size || actual.size == hints.constrain(frame.size))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn arrived_compares_sizes_as_well() {
+    let hints = SizeHints::default();
+    // A resize from the left edge doesn't move the origin.
+    let frame = rect(100, 100, 900, 600);
+    assert!(arrived(&hints, frame, frame));
+    assert!(!arrived(&hints, rect(100, 100, 800, 600), frame));
+    assert!(!arrived(&hints, rect(90, 100, 900, 600), frame));
+  }
+
+  #[test]
+  fn arrived_allows_for_resize_increments() {
+    let hints = SizeHints {
+      resize_inc: Some(ScreenSize::new(10, 20)),
+      ..SizeHints::default()
+    };
+    let frame = rect(100, 100, 905, 610);
+    assert!(arrived(&hints, rect(100, 100, 900, 600), frame));
+    assert!(!arrived(&hints, rect(100, 100, 890, 600), frame));
+  }
+}
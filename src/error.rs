@@ -0,0 +1,116 @@
+use std::fmt;
+
+use xcb::xproto;
+
+use crate::xcb_util::geometry::ScreenRect;
+
+/// Failures that callers (usually shortcut scripts) may want to tell apart.
+///
+/// Each variant has its own exit code; anything else that goes wrong exits
+/// with 1. `NoActiveWindow`, `NoOutputInDirection` and `NoMinimisedWindow` mean
+/// there was nothing to do, rather than that something broke.
+#[derive(Debug)]
+pub enum TileError {
+  NoActiveWindow,
+  NoOutputInDirection,
+  NoOutputForWindow {
+    window: xproto::Window,
+  },
+  ParseError {
+    arg: String,
+    expected: &'static str,
+  },
+  WmUnsupported {
+    atom: &'static str,
+  },
+  WindowRefusedGeometry {
+    window: xproto::Window,
+    requested: ScreenRect,
+    actual: ScreenRect,
+  },
+  XError {
+    request: &'static str,
+    code: u8,
+  },
+  NoMinimisedWindow,
+}
+
+impl TileError {
+  /// Also used when the command line can't be parsed at all.
+  pub const PARSE_ERROR_EXIT_CODE: i32 = 5;
+
+  pub fn exit_code(&self) -> i32 {
+    match self {
+      TileError::NoActiveWindow => 2,
+      TileError::NoOutputInDirection => 3,
+      TileError::NoOutputForWindow { .. } => 4,
+      TileError::ParseError { .. } => TileError::PARSE_ERROR_EXIT_CODE,
+      TileError::WmUnsupported { .. } => 6,
+      TileError::WindowRefusedGeometry { .. } => 7,
+      TileError::XError { .. } => 8,
+      TileError::NoMinimisedWindow => 9,
+    }
+  }
+
+  /// A suggestion for what to do about the error, if there is one.
+  pub fn hint(&self) -> Option<&'static str> {
+    match self {
+      TileError::NoActiveWindow => {
+        Some(
+          "Focus a window first. Some window managers clear _NET_ACTIVE_WINDOW while the desktop \
+           has focus.",
+        )
+      }
+      TileError::NoOutputInDirection => None,
+      TileError::NoOutputForWindow { .. } => {
+        Some("The window is entirely off-screen; move it back onto an output first.")
+      }
+      TileError::ParseError { .. } => None,
+      TileError::WmUnsupported { .. } => {
+        Some("x11-tile needs an EWMH-compliant window manager (KWin, Xfwm, Openbox, ...).")
+      }
+      TileError::WindowRefusedGeometry { .. } => {
+        Some(
+          "Window managers often ignore requests for maximised or tiled windows. Unmaximise the \
+           window first; in KWin, a window rule can force \"Ignore requested geometry\" off. If \
+           the window gets there eventually, raise --wait.",
+        )
+      }
+      TileError::XError { .. } => Some("Run with --trace-x11 to see the requests that were sent."),
+      TileError::NoMinimisedWindow => None,
+    }
+  }
+}
+
+impl fmt::Display for TileError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      TileError::NoActiveWindow => write!(f, "No active window"),
+      TileError::NoOutputInDirection => write!(f, "No output in that direction"),
+      TileError::NoOutputForWindow { window } => {
+        write!(f, "Window {:#x} isn't on any output", window)
+      }
+      TileError::ParseError { arg, expected } => {
+        write!(f, "Couldn't parse `{}`: expected {}", arg, expected)
+      }
+      TileError::WmUnsupported { atom } => write!(f, "Window manager doesn't support {}", atom),
+      TileError::WindowRefusedGeometry {
+        window,
+        requested,
+        actual,
+      } => {
+        write!(
+          f,
+          "Window {:#x} was asked to move to {} but is at {}",
+          window, requested, actual
+        )
+      }
+      TileError::XError { request, code } => {
+        write!(f, "X error {} in response to {}", code, request)
+      }
+      TileError::NoMinimisedWindow => write!(f, "No minimised window on this desktop"),
+    }
+  }
+}
+
+impl std::error::Error for TileError {}
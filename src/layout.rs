@@ -0,0 +1,782 @@
+use std::cmp::{
+  max,
+  min,
+};
+
+use euclid::Vector2D;
+use log::{
+  debug,
+  trace,
+};
+use serde::{
+  Deserialize,
+  Serialize,
+};
+use xcb::randr as xrandr;
+
+use crate::{
+  error::TileError,
+  outputs::OutputSource,
+  xcb_util::geometry::*,
+};
+
+/// The orientation of a CRTC, as reported by RandR.
+///
+/// Reflections are ignored; they don't change the CRTC's rect.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Rotation {
+  Normal,
+  Left,
+  Inverted,
+  Right,
+}
+
+impl Rotation {
+  pub fn from_randr(rotation: u16) -> Rotation {
+    match rotation as u32 {
+      xrandr::ROTATION_ROTATE_90 => Rotation::Left,
+      xrandr::ROTATION_ROTATE_180 => Rotation::Inverted,
+      xrandr::ROTATION_ROTATE_270 => Rotation::Right,
+      _ => Rotation::Normal,
+    }
+  }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Direction {
+  North,
+  South,
+  East,
+  West,
+}
+
+impl std::str::FromStr for Direction {
+  type Err = TileError;
+  fn from_str(s: &str) -> Result<Direction, TileError> {
+    match s {
+      "h" => Ok(Direction::West),
+      "j" => Ok(Direction::South),
+      "k" => Ok(Direction::North),
+      "l" => Ok(Direction::East),
+      _ => {
+        Err(TileError::ParseError {
+          arg: s.to_owned(),
+          expected: "a direction - one of hjkl",
+        })
+      }
+    }
+  }
+}
+
+impl Direction {
+  pub fn opposite(self) -> Direction {
+    match self {
+      Direction::North => Direction::South,
+      Direction::South => Direction::North,
+      Direction::East => Direction::West,
+      Direction::West => Direction::East,
+    }
+  }
+
+  fn vector(self) -> Vector2D<f32, ScreenSpace> {
+    match self {
+      Direction::West => Vector2D::new(-1.0, 0.0),
+      Direction::South => Vector2D::new(0.0, 1.0),
+      Direction::North => Vector2D::new(0.0, -1.0),
+      Direction::East => Vector2D::new(1.0, 0.0),
+    }
+  }
+
+  /// 1 if coordinates increase going this way, otherwise -1.
+  pub fn sign(self) -> i32 {
+    match self {
+      Direction::West | Direction::North => -1,
+      Direction::East | Direction::South => 1,
+    }
+  }
+
+  /// The coordinate of the side of `rect` facing this way.
+  pub fn leading_edge(self, rect: ScreenRect) -> i32 {
+    match self {
+      Direction::West => rect.min_x(),
+      Direction::East => rect.max_x(),
+      Direction::North => rect.min_y(),
+      Direction::South => rect.max_y(),
+    }
+  }
+
+  /// Whether `a` reaches further this way than `b` does.
+  fn reaches_past(self, a: ScreenRect, b: ScreenRect) -> bool {
+    let (a, b) = (self.leading_edge(a), self.leading_edge(b));
+    match self {
+      Direction::West | Direction::North => a < b,
+      Direction::East | Direction::South => a > b,
+    }
+  }
+
+  /// `rect` with the side facing this way moved to `edge`, and the opposite
+  /// side left where it is.
+  pub fn with_leading_edge(self, rect: ScreenRect, edge: i32) -> ScreenRect {
+    let mut b = rect.to_box2d();
+    match self {
+      Direction::West => b.min.x = edge,
+      Direction::East => b.max.x = edge,
+      Direction::North => b.min.y = edge,
+      Direction::South => b.max.y = edge,
+    }
+    b.to_rect()
+  }
+
+  /// A vector `distance` long, going this way.
+  pub fn offset(self, distance: i32) -> Vector2D<i32, ScreenSpace> {
+    match self {
+      Direction::West | Direction::East => Vector2D::new(self.sign() * distance, 0),
+      Direction::North | Direction::South => Vector2D::new(0, self.sign() * distance),
+    }
+  }
+
+  /// How far `size` goes along this direction.
+  pub fn length(self, size: ScreenSize) -> i32 {
+    match self {
+      Direction::West | Direction::East => size.width,
+      Direction::North | Direction::South => size.height,
+    }
+  }
+
+  /// The range `rect` covers across this direction.
+  pub fn cross_span(self, rect: ScreenRect) -> (i32, i32) {
+    match self {
+      Direction::West | Direction::East => (rect.min_y(), rect.max_y()),
+      Direction::North | Direction::South => (rect.min_x(), rect.max_x()),
+    }
+  }
+}
+
+/// How much two ranges overlap, or how far apart they are as a negative
+/// number.
+pub fn overlap(a: (i32, i32), b: (i32, i32)) -> i32 { min(a.1, b.1) - max(a.0, b.0) }
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Edge {
+  Left,
+  Right,
+  Top,
+  Bottom,
+}
+
+/// An area of the root window reserved by a dock or panel via
+/// `_NET_WM_STRUT_PARTIAL`, in root window coordinates.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Strut {
+  pub edge: Edge,
+  pub rect: ScreenRect,
+}
+
+impl Strut {
+  /// Converts a `_NET_WM_STRUT_PARTIAL` property value into the (up to four)
+  /// areas it reserves. Strut widths are measured from the edges of the root
+  /// window, not from the edges of any particular output.
+  pub fn from_partial(value: &[u32], root: ScreenRect) -> Vec<Strut> {
+    let v = |i: usize| value[i] as i32;
+    let span = |start: i32, end: i32| (start, end - start + 1);
+
+    let mut struts = vec![];
+    if value.len() < 12 {
+      return struts;
+    }
+
+    if v(0) > 0 {
+      let (y, height) = span(v(4), v(5));
+      struts.push(Strut {
+        edge: Edge::Left,
+        rect: ScreenRect::new(
+          ScreenPoint::new(root.min_x(), y),
+          ScreenSize::new(v(0), height),
+        ),
+      });
+    }
+    if v(1) > 0 {
+      let (y, height) = span(v(6), v(7));
+      struts.push(Strut {
+        edge: Edge::Right,
+        rect: ScreenRect::new(
+          ScreenPoint::new(root.max_x() - v(1), y),
+          ScreenSize::new(v(1), height),
+        ),
+      });
+    }
+    if v(2) > 0 {
+      let (x, width) = span(v(8), v(9));
+      struts.push(Strut {
+        edge: Edge::Top,
+        rect: ScreenRect::new(
+          ScreenPoint::new(x, root.min_y()),
+          ScreenSize::new(width, v(2)),
+        ),
+      });
+    }
+    if v(3) > 0 {
+      let (x, width) = span(v(10), v(11));
+      struts.push(Strut {
+        edge: Edge::Bottom,
+        rect: ScreenRect::new(
+          ScreenPoint::new(x, root.max_y() - v(3)),
+          ScreenSize::new(width, v(3)),
+        ),
+      });
+    }
+
+    struts
+  }
+
+  /// Shrinks `frame` so that it no longer overlaps this strut. Struts that
+  /// don't overlap `frame` leave it unchanged.
+  pub fn inset(&self, frame: ScreenRect) -> ScreenRect {
+    let overlap = match frame.intersection(&self.rect) {
+      Some(overlap) => overlap,
+      None => return frame,
+    };
+
+    let mut b = frame.to_box2d();
+    match self.edge {
+      Edge::Left => b.min.x = max(b.min.x, overlap.max_x()),
+      Edge::Right => b.max.x = min(b.max.x, overlap.min_x()),
+      Edge::Top => b.min.y = max(b.min.y, overlap.max_y()),
+      Edge::Bottom => b.max.y = min(b.max.y, overlap.min_y()),
+    }
+    b.to_rect()
+  }
+}
+
+/// Where an output's usable rect comes from.
+#[derive(Clone, Copy, Debug)]
+pub enum UsableArea {
+  /// The WM's work area for the current desktop, where there's only one
+  /// output; otherwise the strut walk, since a single work area rect can't
+  /// describe panels on some monitors and not others.
+  Auto,
+  /// The output minus the struts of docks and panels on it.
+  Struts,
+  /// The output's part of the WM's work area for the current desktop. The
+  /// work area is one rect covering every output, so this can be wrong when a
+  /// panel is on only some of them.
+  WorkArea,
+  /// The whole output.
+  Output,
+}
+
+impl std::str::FromStr for UsableArea {
+  type Err = TileError;
+  fn from_str(s: &str) -> Result<UsableArea, TileError> {
+    match s {
+      "auto" => Ok(UsableArea::Auto),
+      "struts" => Ok(UsableArea::Struts),
+      "workarea" => Ok(UsableArea::WorkArea),
+      "output" => Ok(UsableArea::Output),
+      _ => {
+        Err(TileError::ParseError {
+          arg: s.to_owned(),
+          expected: "one of auto, struts, workarea or output",
+        })
+      }
+    }
+  }
+}
+
+/// A connected RandR output with an active CRTC, or whatever stands in for
+/// one when RandR isn't available (see `outputs::discover`).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Output {
+  pub name: String,
+  /// The output's rect in root window coordinates (already rotated).
+  pub frame: ScreenRect,
+  pub rotation: Rotation,
+  pub primary: bool,
+  /// The part of `frame` windows should go in: the whole frame, the frame
+  /// minus any struts on it, or its part of the WM's work area, depending on
+  /// `UsableArea`.
+  pub usable: ScreenRect,
+  /// Defined in the config file rather than found on the display.
+  #[serde(default)]
+  pub configured: bool,
+}
+
+/// Every connected output, along with the WM's per-desktop work areas and the
+/// struts used to compute each output's usable rect.
+///
+/// This is built once per run; commands should look up outputs here rather
+/// than querying RandR or walking struts themselves.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OutputLayout {
+  #[serde(default)]
+  pub source: OutputSource,
+  pub outputs: Vec<Output>,
+  pub work_areas: Vec<ScreenRect>,
+  pub struts: Vec<Strut>,
+}
+
+impl OutputLayout {
+  /// Builds a layout from the raw output rects. Each output's usable rect is
+  /// its whole frame until `set_usable_area` is called.
+  pub fn new(
+    source: OutputSource,
+    outputs: Vec<Output>,
+    work_areas: Vec<ScreenRect>,
+    struts: Vec<Strut>,
+  ) -> OutputLayout {
+    OutputLayout {
+      source,
+      outputs,
+      work_areas,
+      struts,
+    }
+  }
+
+  /// Recomputes each output's usable rect. `current_desktop` picks the entry
+  /// of `_NET_WORKAREA` to use.
+  pub fn set_usable_area(&mut self, strategy: UsableArea, current_desktop: Option<u32>) {
+    // WMs without desktops may still set a single work area.
+    let work_area = current_desktop
+      .and_then(|desktop| self.work_areas.get(desktop as usize))
+      .or_else(|| self.work_areas.first())
+      .copied();
+
+    let strategy = match strategy {
+      UsableArea::Auto if self.outputs.len() == 1 && work_area.is_some() => UsableArea::WorkArea,
+      UsableArea::Auto => UsableArea::Struts,
+      strategy => strategy,
+    };
+
+    for o in &mut self.outputs {
+      let inset = self
+        .struts
+        .iter()
+        .fold(o.frame, |frame, strut| strut.inset(frame));
+      o.usable = match strategy {
+        UsableArea::Output => o.frame,
+        UsableArea::Struts | UsableArea::Auto => inset,
+        // The work area is a single rect spanning every output, so it can
+        // only narrow an output down. If the two don't overlap at all the WM's
+        // value is probably stale, and the strut walk is used instead.
+        UsableArea::WorkArea => {
+          work_area
+            .and_then(|work_area| o.frame.intersection(&work_area))
+            .unwrap_or(inset)
+        }
+      };
+      debug!(
+        "Output {}: {} ({:?}{}), usable {}",
+        o.name,
+        o.frame,
+        o.rotation,
+        if o.primary { ", primary" } else { "" },
+        o.usable
+      );
+    }
+  }
+
+  /// The output next to `from` in `direction`, if there is one.
+  ///
+  /// Outputs that share a border with `from` are preferred; when several do,
+  /// the one alongside most of `window` wins, so a window at the top of a
+  /// monitor goes to the upper of two stacked neighbours. Without a shared
+  /// border, the output whose center is closest ahead of `from`'s is used,
+  /// as long as it reaches further that way than `from` does.
+  pub fn neighbour(
+    &self,
+    from: &Output,
+    window: ScreenRect,
+    direction: Direction,
+  ) -> Option<&Output> {
+    self
+      .adjacent(from, window, direction)
+      .or_else(|| self.nearest_ahead(from, direction))
+  }
+
+  /// The output reached by going from `from` in `direction` until there are
+  /// no outputs left, or None if there's nothing that way at all.
+  pub fn far_end<'a>(
+    &'a self,
+    from: &'a Output,
+    window: ScreenRect,
+    direction: Direction,
+  ) -> Option<&'a Output> {
+    let mut current = from;
+    // Bounded, in case overlapping outputs make the neighbours go in circles.
+    for _ in 0..self.outputs.len() {
+      match self.neighbour(current, window, direction) {
+        Some(next) => current = next,
+        None => break,
+      }
+    }
+    if std::ptr::eq(current, from) {
+      None
+    } else {
+      trace!(output = current.name.as_str(); "Wrapping around");
+      Some(current)
+    }
+  }
+
+  fn adjacent(&self, from: &Output, window: ScreenRect, direction: Direction) -> Option<&Output> {
+    let edge = direction.leading_edge(from.frame);
+    let from_span = direction.cross_span(from.frame);
+    let window_span = direction.cross_span(window);
+
+    self
+      .outputs
+      .iter()
+      .filter(|o| direction.opposite().leading_edge(o.frame) == edge)
+      .filter(|o| overlap(direction.cross_span(o.frame), from_span) > 0)
+      .map(|o| {
+        let span = direction.cross_span(o.frame);
+        let score = (overlap(span, window_span), overlap(span, from_span));
+        trace!(
+          output = o.name.as_str(),
+          window_overlap = score.0,
+          output_overlap = score.1;
+          "Adjacent output"
+        );
+        (o, score)
+      })
+      .max_by_key(|(_, score)| *score)
+      .map(|(o, _)| o)
+  }
+
+  fn nearest_ahead(&self, from: &Output, direction: Direction) -> Option<&Output> {
+    let ahead = self
+      .outputs
+      .iter()
+      .filter(|o| direction.reaches_past(o.frame, from.frame));
+    let direction = direction.vector();
+    ahead.fold(None, |init: Option<&Output>, output| {
+      let vec = (output.frame.center() - from.frame.center()).cast::<f32>();
+      let old = init.map(|init| (init.frame.center() - from.frame.center()).cast::<f32>());
+
+      let projection = vec.dot(direction);
+
+      match old {
+        None if projection > 0.0 => {
+          trace!(output = output.name.as_str(), projection = projection; "Starting with output");
+          Some(output)
+        }
+        Some(old) if projection < old.dot(direction) && projection > 0.0 => {
+          trace!(
+            output = output.name.as_str(),
+            projection = projection,
+            replaced_projection = old.dot(direction);
+            "Replacing closer output"
+          );
+          Some(output)
+        }
+        _ => {
+          trace!(output = output.name.as_str(), projection = projection; "Ignoring output");
+          init
+        }
+      }
+    })
+  }
+
+  /// The output that `frame` overlaps the most, if any.
+  pub fn output_for(&self, frame: ScreenRect) -> Option<&Output> {
+    self
+      .outputs
+      .iter()
+      .filter_map(|o| o.frame.intersection(&frame).map(|i| (o, i.area())))
+      .max_by_key(|(_, area)| *area)
+      .map(|(o, _)| o)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn layout(outputs: &[(&str, ScreenRect)]) -> OutputLayout {
+    let outputs = outputs
+      .iter()
+      .map(|(name, frame)| {
+        Output {
+          name: name.to_string(),
+          frame: *frame,
+          rotation: Rotation::Normal,
+          primary: false,
+          usable: *frame,
+          configured: false,
+        }
+      })
+      .collect();
+    OutputLayout::new(OutputSource::default(), outputs, vec![], vec![])
+  }
+
+  fn neighbour(
+    layout: &OutputLayout,
+    from: usize,
+    window: ScreenRect,
+    direction: Direction,
+  ) -> Option<&str> {
+    layout
+      .neighbour(&layout.outputs[from], window, direction)
+      .map(|o| o.name.as_str())
+  }
+
+  fn far_end(layout: &OutputLayout, from: usize, direction: Direction) -> Option<&str> {
+    let from = &layout.outputs[from];
+    layout
+      .far_end(from, from.frame, direction)
+      .map(|o| o.name.as_str())
+  }
+
+  /// A portrait monitor on the left, beside two landscape ones stacked on
+  /// top of each other.
+  fn portrait_beside_two_landscape() -> OutputLayout {
+    layout(&[
+      ("portrait", rect(0, 0, 1080, 1920)),
+      ("upper", rect(1080, 0, 1920, 1080)),
+      ("lower", rect(1080, 1080, 1920, 1080)),
+    ])
+  }
+
+  #[test]
+  fn adjacent_output_alongside_the_window_wins() {
+    let layout = portrait_beside_two_landscape();
+    let top = rect(100, 100, 800, 400);
+    let bottom = rect(100, 1400, 800, 400);
+    assert_eq!(neighbour(&layout, 0, top, Direction::East), Some("upper"));
+    assert_eq!(
+      neighbour(&layout, 0, bottom, Direction::East),
+      Some("lower")
+    );
+  }
+
+  #[test]
+  fn window_outside_every_neighbour_goes_to_the_closest() {
+    // Neither landscape monitor is alongside a window below the end of both,
+    // so the one nearer to it wins.
+    let layout = layout(&[
+      ("portrait", rect(0, 0, 1080, 1920)),
+      ("upper", rect(1080, 0, 1920, 1080)),
+      ("lower", rect(1080, 1080, 1920, 600)),
+    ]);
+    let window = rect(100, 1700, 800, 200);
+    assert_eq!(
+      neighbour(&layout, 0, window, Direction::East),
+      Some("lower")
+    );
+  }
+
+  #[test]
+  fn both_landscape_monitors_lead_back_to_the_portrait_one() {
+    let layout = portrait_beside_two_landscape();
+    let window = rect(1200, 100, 800, 400);
+    assert_eq!(
+      neighbour(&layout, 1, window, Direction::West),
+      Some("portrait")
+    );
+    let window = rect(1200, 1200, 800, 400);
+    assert_eq!(
+      neighbour(&layout, 2, window, Direction::West),
+      Some("portrait")
+    );
+    assert_eq!(
+      neighbour(&layout, 1, window, Direction::South),
+      Some("lower")
+    );
+    assert_eq!(
+      neighbour(&layout, 2, window, Direction::North),
+      Some("upper")
+    );
+    assert_eq!(neighbour(&layout, 1, window, Direction::East), None);
+  }
+
+  #[test]
+  fn vertically_offset_outputs_are_adjacent() {
+    let layout = layout(&[
+      ("left", rect(0, 0, 1920, 1080)),
+      ("right", rect(1920, 400, 1920, 1080)),
+    ]);
+    let window = rect(100, 100, 800, 200);
+    assert_eq!(
+      neighbour(&layout, 0, window, Direction::East),
+      Some("right")
+    );
+    let window = rect(2000, 1200, 800, 200);
+    assert_eq!(neighbour(&layout, 1, window, Direction::West), Some("left"));
+  }
+
+  #[test]
+  fn outputs_with_a_gap_fall_back_to_the_nearest_ahead() {
+    let layout = layout(&[
+      ("left", rect(0, 0, 1920, 1080)),
+      ("right", rect(2000, 0, 1920, 1080)),
+      ("below", rect(0, 1200, 1920, 1080)),
+    ]);
+    let window = rect(100, 100, 800, 200);
+    assert_eq!(
+      neighbour(&layout, 0, window, Direction::East),
+      Some("right")
+    );
+    assert_eq!(
+      neighbour(&layout, 0, window, Direction::South),
+      Some("below")
+    );
+    assert_eq!(neighbour(&layout, 0, window, Direction::West), None);
+  }
+
+  #[test]
+  fn far_end_wraps_across_the_row() {
+    let layout = layout(&[
+      ("a", rect(0, 0, 1920, 1080)),
+      ("b", rect(1920, 0, 1920, 1080)),
+      ("c", rect(3840, 0, 1920, 1080)),
+    ]);
+    assert_eq!(far_end(&layout, 2, Direction::West), Some("a"));
+    assert_eq!(far_end(&layout, 0, Direction::East), Some("c"));
+    assert_eq!(far_end(&layout, 1, Direction::West), Some("a"));
+    assert_eq!(far_end(&layout, 0, Direction::West), None);
+  }
+
+  #[test]
+  fn far_end_of_the_portrait_layout() {
+    let layout = portrait_beside_two_landscape();
+    assert_eq!(far_end(&layout, 2, Direction::West), Some("portrait"));
+    assert_eq!(far_end(&layout, 1, Direction::South), Some("lower"));
+    assert_eq!(far_end(&layout, 0, Direction::West), None);
+  }
+
+  fn usable(layout: &OutputLayout) -> Vec<ScreenRect> {
+    layout.outputs.iter().map(|o| o.usable).collect()
+  }
+
+  #[test]
+  fn struts_on_each_side() {
+    let root = rect(0, 0, 3840, 1080);
+    let strut = |edge, rect| Strut { edge, rect };
+    assert_eq!(
+      Strut::from_partial(
+        &[50, 60, 28, 40, 0, 1079, 100, 599, 0, 1919, 1920, 3839],
+        root
+      ),
+      [
+        strut(Edge::Left, rect(0, 0, 50, 1080)),
+        strut(Edge::Right, rect(3780, 100, 60, 500)),
+        strut(Edge::Top, rect(0, 0, 1920, 28)),
+        strut(Edge::Bottom, rect(1920, 1040, 1920, 40)),
+      ]
+    );
+  }
+
+  #[test]
+  fn short_or_empty_struts_reserve_nothing() {
+    let root = rect(0, 0, 1920, 1080);
+    assert!(Strut::from_partial(&[0; 12], root).is_empty());
+    assert!(Strut::from_partial(&[0, 0, 28, 0], root).is_empty());
+  }
+
+  #[test]
+  fn inset_by_struts_on_each_side() {
+    let left = rect(0, 0, 1920, 1080);
+    let right = rect(1920, 0, 1920, 1080);
+    let strut = |edge, rect| Strut { edge, rect };
+    assert_eq!(
+      strut(Edge::Left, rect(0, 0, 50, 1080)).inset(left),
+      rect(50, 0, 1870, 1080)
+    );
+    assert_eq!(
+      strut(Edge::Top, rect(0, 0, 1920, 28)).inset(left),
+      rect(0, 28, 1920, 1052)
+    );
+    assert_eq!(
+      strut(Edge::Right, rect(3780, 100, 60, 500)).inset(right),
+      rect(1920, 0, 1860, 1080)
+    );
+    assert_eq!(
+      strut(Edge::Bottom, rect(1920, 1040, 1920, 40)).inset(right),
+      rect(1920, 0, 1920, 1040)
+    );
+    // A panel on the other output doesn't get in the way.
+    assert_eq!(strut(Edge::Top, rect(0, 0, 1920, 28)).inset(right), right);
+  }
+
+  /// Two outputs side by side with a panel along the top of the left one, and
+  /// a work area for each of two desktops. The WM can only describe the panel
+  /// as a strip across the top of both.
+  fn two_outputs_with_a_panel() -> OutputLayout {
+    OutputLayout {
+      work_areas: vec![rect(0, 28, 3840, 1052), rect(0, 0, 3840, 1080)],
+      struts: vec![Strut {
+        edge: Edge::Top,
+        rect: rect(0, 0, 1920, 28),
+      }],
+      ..layout(&[
+        ("left", rect(0, 0, 1920, 1080)),
+        ("right", rect(1920, 0, 1920, 1080)),
+      ])
+    }
+  }
+
+  #[test]
+  fn usable_area_output_is_the_whole_output() {
+    let mut layout = two_outputs_with_a_panel();
+    layout.set_usable_area(UsableArea::Output, Some(0));
+    assert_eq!(
+      usable(&layout),
+      [rect(0, 0, 1920, 1080), rect(1920, 0, 1920, 1080)]
+    );
+  }
+
+  #[test]
+  fn usable_area_struts_only_insets_outputs_with_panels() {
+    let mut layout = two_outputs_with_a_panel();
+    layout.set_usable_area(UsableArea::Struts, Some(0));
+    assert_eq!(
+      usable(&layout),
+      [rect(0, 28, 1920, 1052), rect(1920, 0, 1920, 1080)]
+    );
+  }
+
+  #[test]
+  fn usable_area_workarea_uses_the_current_desktop() {
+    let mut layout = two_outputs_with_a_panel();
+    layout.set_usable_area(UsableArea::WorkArea, Some(0));
+    assert_eq!(
+      usable(&layout),
+      [rect(0, 28, 1920, 1052), rect(1920, 28, 1920, 1052)]
+    );
+    layout.set_usable_area(UsableArea::WorkArea, Some(1));
+    assert_eq!(
+      usable(&layout),
+      [rect(0, 0, 1920, 1080), rect(1920, 0, 1920, 1080)]
+    );
+  }
+
+  #[test]
+  fn usable_area_workarea_ignores_a_work_area_off_the_output() {
+    let mut layout = two_outputs_with_a_panel();
+    layout.work_areas = vec![rect(5000, 0, 100, 100)];
+    layout.set_usable_area(UsableArea::WorkArea, Some(0));
+    assert_eq!(
+      usable(&layout),
+      [rect(0, 28, 1920, 1052), rect(1920, 0, 1920, 1080)]
+    );
+  }
+
+  #[test]
+  fn usable_area_auto_uses_struts_with_several_outputs() {
+    let mut layout = two_outputs_with_a_panel();
+    layout.set_usable_area(UsableArea::Auto, Some(0));
+    assert_eq!(
+      usable(&layout),
+      [rect(0, 28, 1920, 1052), rect(1920, 0, 1920, 1080)]
+    );
+  }
+
+  #[test]
+  fn usable_area_auto_uses_the_work_area_with_one_output() {
+    let mut layout = OutputLayout {
+      work_areas: vec![rect(0, 0, 1920, 1040)],
+      ..layout(&[("only", rect(0, 0, 1920, 1080))])
+    };
+    layout.set_usable_area(UsableArea::Auto, None);
+    assert_eq!(usable(&layout), [rect(0, 0, 1920, 1040)]);
+  }
+}
@@ -0,0 +1,139 @@
+use std::{
+  env,
+  io::Write,
+};
+
+use log::{
+  kv::{
+    self,
+    VisitSource,
+  },
+  LevelFilter,
+  Record,
+};
+use serde_json::{
+  Map,
+  Value,
+};
+
+use crate::{
+  error::TileError,
+  xcb_util::trace,
+};
+
+pub enum LogFormat {
+  Text,
+  Json,
+}
+
+impl std::str::FromStr for LogFormat {
+  type Err = TileError;
+  fn from_str(s: &str) -> Result<LogFormat, TileError> {
+    match s {
+      "text" => Ok(LogFormat::Text),
+      "json" => Ok(LogFormat::Json),
+      _ => {
+        Err(TileError::ParseError {
+          arg: s.to_owned(),
+          expected: "text or json",
+        })
+      }
+    }
+  }
+}
+
+/// Collects a record's key-value pairs, in the order they were logged.
+/// Numbers and booleans are kept as such for JSON output; anything else is
+/// formatted as a string.
+struct Fields(Vec<(String, Value)>);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+  fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+    let value = if let Some(b) = value.to_bool() {
+      Value::from(b)
+    } else if let Some(n) = value.to_u64() {
+      Value::from(n)
+    } else if let Some(n) = value.to_i64() {
+      Value::from(n)
+    } else if let Some(n) = value.to_f64() {
+      Value::from(n)
+    } else {
+      Value::from(value.to_string())
+    };
+    self.0.push((key.to_string(), value));
+    Ok(())
+  }
+}
+
+impl Fields {
+  fn of(record: &Record) -> Fields {
+    let mut fields = Fields(vec![]);
+    // Collecting into a Vec can't fail.
+    let _ = record.key_values().visit(&mut fields);
+    fields
+  }
+}
+
+/// Sets up logging to stderr.
+///
+/// `verbosity` is the number of `-v` flags minus the number of `-q` flags;
+/// warnings and errors are shown by default. `RUST_LOG` can still be used to
+/// set levels per module, and takes precedence.
+pub fn init(verbosity: i32, format: &LogFormat, trace_x11: bool) {
+  let level = match verbosity {
+    i32::MIN..=-2 => LevelFilter::Off,
+    -1 => LevelFilter::Error,
+    0 => LevelFilter::Warn,
+    1 => LevelFilter::Info,
+    2 => LevelFilter::Debug,
+    _ => LevelFilter::Trace,
+  };
+
+  let mut builder = env_logger::Builder::new();
+  builder.filter_level(level);
+  // X tracing is logged at trace level, so it needs turning on separately from
+  // the tool's own logging.
+  builder.filter_module(
+    trace::TARGET,
+    if trace_x11 { LevelFilter::Trace } else { level },
+  );
+  if let Ok(filters) = env::var("RUST_LOG") {
+    builder.parse_filters(&filters);
+  }
+
+  match format {
+    LogFormat::Text => {
+      builder.format(|buf, record| {
+        write!(
+          buf,
+          "[{} {}] {}",
+          record.level(),
+          record.target(),
+          record.args()
+        )?;
+        for (key, value) in Fields::of(record).0 {
+          // Strings are written unquoted, everything else as JSON.
+          match value {
+            Value::String(value) => write!(buf, " {}={}", key, value)?,
+            value => write!(buf, " {}={}", key, value)?,
+          }
+        }
+        writeln!(buf)
+      })
+    }
+    LogFormat::Json => {
+      builder.format(|buf, record| {
+        let mut object = Map::new();
+        object.insert("level".into(), record.level().to_string().into());
+        object.insert("target".into(), record.target().into());
+        object.insert("message".into(), record.args().to_string().into());
+        for (key, value) in Fields::of(record).0 {
+          object.insert(key, value);
+        }
+        writeln!(buf, "{}", Value::Object(object))
+      })
+    }
+  };
+
+  builder.init();
+}
@@ -0,0 +1,574 @@
+//! Discovery of the outputs on a screen.
+//!
+//! Not every X server has a working RandR: Xvfb, Xephyr, VNC servers and
+//! nested sessions often report no CRTCs at all. Providers are tried in order
+//! of how much they can tell us, and the first one that finds any outputs
+//! wins.
+
+use std::fmt;
+
+use anyhow::{
+  anyhow,
+  Error,
+};
+use log::{
+  debug,
+  warn,
+};
+use serde::{
+  Deserialize,
+  Serialize,
+};
+#[cfg(feature = "xinerama")]
+use xcb::xinerama;
+use xcb::{
+  base as xbase,
+  randr as xrandr,
+  xproto,
+};
+
+use crate::{
+  config::{
+    Config,
+    Split,
+    VirtualMonitor,
+  },
+  layout::{
+    Output,
+    Rotation,
+  },
+  xcb_util::{
+    connection::ConnectionExt,
+    geometry::*,
+    randr_monitors::{
+      self,
+      GetMonitorsCookieExt,
+    },
+    trace::{
+      self,
+      reply,
+    },
+  },
+};
+
+/// Where a screen's outputs came from.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum OutputSource {
+  RandrMonitors,
+  RandrCrtcs,
+  Xinerama,
+  /// No extension was any help, so the whole root window is one output.
+  Root,
+}
+
+impl Default for OutputSource {
+  /// Snapshots taken before there was a choice all came from RandR.
+  fn default() -> OutputSource { OutputSource::RandrCrtcs }
+}
+
+impl fmt::Display for OutputSource {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      OutputSource::RandrMonitors => write!(f, "RandR monitors"),
+      OutputSource::RandrCrtcs => write!(f, "RandR CRTCs"),
+      OutputSource::Xinerama => write!(f, "Xinerama"),
+      OutputSource::Root => write!(f, "root window"),
+    }
+  }
+}
+
+type Provider = fn(&xbase::Connection, xproto::Window) -> Result<Vec<Output>, Error>;
+
+/// Finds the outputs on the screen with root window `root`, trying each
+/// provider in turn.
+pub fn discover(
+  conn: &xbase::Connection,
+  root: xproto::Window,
+  root_frame: ScreenRect,
+) -> Result<(OutputSource, Vec<Output>), Error> {
+  let providers: [(OutputSource, Provider); 3] = [
+    (OutputSource::RandrMonitors, randr_monitors),
+    (OutputSource::RandrCrtcs, randr_crtcs),
+    (OutputSource::Xinerama, xinerama_screens),
+  ];
+
+  for (source, provider) in providers.iter() {
+    match provider(conn, root) {
+      Ok(outputs) if !outputs.is_empty() => {
+        debug!(source:% = source, count = outputs.len(); "Found outputs");
+        return Ok((*source, outputs));
+      }
+      Ok(_) => debug!(source:% = source; "No outputs found"),
+      Err(e) => debug!(source:% = source; "Couldn't get outputs: {:#}", e),
+    }
+  }
+
+  debug!("Treating the root window as a single output");
+  Ok((
+    OutputSource::Root,
+    vec![Output {
+      name: "root".to_owned(),
+      frame: root_frame,
+      rotation: Rotation::Normal,
+      primary: true,
+      usable: root_frame,
+      configured: false,
+    }],
+  ))
+}
+
+/// Applies the config's splits and then its virtual monitors to the outputs
+/// that were found on the display.
+pub fn apply_config(outputs: Vec<Output>, config: &Config) -> Vec<Output> {
+  for split in &config.splits {
+    if !outputs.iter().any(|o| o.name == split.output) {
+      warn!(
+        "Can't split {}: there's no output with that name",
+        split.output
+      );
+    }
+  }
+
+  let outputs = outputs
+    .into_iter()
+    .flat_map(|o| {
+      match config.splits.iter().find(|split| split.output == o.name) {
+        Some(split) => split_output(&o, split),
+        None => vec![o],
+      }
+    })
+    .collect();
+  add_monitors(outputs, &config.monitors)
+}
+
+/// Divides `extent` pixels from `start` into spans with relative sizes
+/// `weights`. Rounding is done on the edges, so the spans always add up to
+/// `extent` exactly.
+fn spans(start: i32, extent: i32, weights: &[u32]) -> Vec<(i32, i32)> {
+  let total = weights.iter().sum::<u32>() as i64;
+  let mut sum = 0;
+  let mut edges = vec![start];
+  for w in weights {
+    sum += *w as i64;
+    edges.push(start + (extent as i64 * sum / total) as i32);
+  }
+  edges
+    .windows(2)
+    .map(|edge| (edge[0], edge[1] - edge[0]))
+    .filter(|(_, length)| *length > 0)
+    .collect()
+}
+
+/// The regions `split` divides `output` into. The region containing the
+/// output's center inherits its primary flag.
+fn split_output(output: &Output, split: &Split) -> Vec<Output> {
+  let weights = |weights: &[u32]| {
+    if weights.iter().sum::<u32>() == 0 {
+      vec![1]
+    } else {
+      weights.to_vec()
+    }
+  };
+  let columns = spans(
+    output.frame.min_x(),
+    output.frame.width(),
+    &weights(&split.columns),
+  );
+  let rows = spans(
+    output.frame.min_y(),
+    output.frame.height(),
+    &weights(&split.rows),
+  );
+
+  let center = output.frame.center();
+  rows
+    .iter()
+    .flat_map(|(y, height)| columns.iter().map(move |(x, width)| (*x, *y, *width, *height)))
+    .enumerate()
+    .map(|(i, (x, y, width, height))| {
+      let frame = ScreenRect::new(ScreenPoint::new(x, y), ScreenSize::new(width, height));
+      let name = split
+        .names
+        .get(i)
+        .cloned()
+        .unwrap_or_else(|| format!("{}:{}", output.name, i + 1));
+      debug!(output = output.name.as_str(), region = name.as_str(), frame:% = frame; "Split output");
+      Output {
+        name,
+        frame,
+        rotation: output.rotation,
+        primary: output.primary && frame.contains(center),
+        usable: frame,
+        configured: true,
+      }
+    })
+    .collect()
+}
+
+/// Replaces every output that a configured monitor overlaps with the
+/// configured monitors. Each takes its rotation from the output its center is
+/// on, and the first on the primary output becomes primary.
+fn add_monitors(outputs: Vec<Output>, monitors: &[VirtualMonitor]) -> Vec<Output> {
+  if monitors.is_empty() {
+    return outputs;
+  }
+
+  let mut configured = monitors
+    .iter()
+    .map(|m| {
+      let frame = m.frame();
+      let parent = outputs.iter().find(|o| o.frame.contains(frame.center()));
+      Output {
+        name: m.name.clone(),
+        frame,
+        rotation: parent.map_or(Rotation::Normal, |o| o.rotation),
+        primary: false,
+        usable: frame,
+        configured: true,
+      }
+    })
+    .collect::<Vec<_>>();
+  if let Some(primary) = outputs.iter().find(|o| o.primary) {
+    if let Some(m) = configured
+      .iter_mut()
+      .find(|m| primary.frame.contains(m.frame.center()))
+    {
+      m.primary = true;
+    }
+  }
+
+  let mut outputs = outputs
+    .into_iter()
+    .filter(|o| {
+      let replaced = configured.iter().any(|m| o.frame.intersects(&m.frame));
+      if replaced {
+        debug!(output = o.name.as_str(); "Replaced by configured monitors");
+      }
+      !replaced
+    })
+    .collect::<Vec<_>>();
+  outputs.append(&mut configured);
+  outputs
+}
+
+fn extension_present(conn: &xbase::Connection, ext: &mut xbase::Extension) -> bool {
+  conn
+    .get_extension_data(ext)
+    .is_some_and(|data| data.present())
+}
+
+/// The RandR version both we and the server support.
+fn randr_version(conn: &xbase::Connection) -> Result<(u32, u32), Error> {
+  if !extension_present(conn, xrandr::id()) {
+    return Err(anyhow!("RandR isn't available"));
+  }
+
+  let version = trace::sent(
+    xrandr::query_version(conn, 1, 5),
+    "RRQueryVersion",
+    format_args!("1.5"),
+  );
+  let version = reply!(version)?;
+  Ok((version.major_version(), version.minor_version()))
+}
+
+/// RandR 1.5 monitors. Unlike CRTCs, these cover tiled displays that need
+/// several CRTCs, and regions defined with `xrandr --setmonitor`. Their frames
+/// are already rotated, but the rotation itself isn't reported, so it's taken
+/// from the CRTCs.
+fn randr_monitors(conn: &xbase::Connection, root: xproto::Window) -> Result<Vec<Output>, Error> {
+  let version = randr_version(conn)?;
+  if version < (1, 5) {
+    return Err(anyhow!(
+      "RandR {}.{} doesn't have monitors",
+      version.0,
+      version.1
+    ));
+  }
+
+  let monitors = trace::sent(
+    randr_monitors::get_monitors(conn, root, true),
+    "RRGetMonitors",
+    format_args!("{:#x}", root),
+  );
+  let monitors = reply!(monitors)?;
+  let names = conn.get_atom_names(&monitors.iter().map(|m| m.name).collect::<Vec<_>>())?;
+  let crtcs = randr_crtcs(conn, root).unwrap_or_else(|e| {
+    debug!("Can't find the monitors' rotations: {}", e);
+    vec![]
+  });
+
+  Ok(
+    monitors
+      .iter()
+      .zip(names)
+      .map(|(m, name)| {
+        Output {
+          name,
+          frame: m.frame,
+          rotation: rotation_of(m.frame, &crtcs),
+          primary: m.primary,
+          usable: m.frame,
+          configured: false,
+        }
+      })
+      .collect(),
+  )
+}
+
+/// The rotation of the CRTC showing the middle of a monitor at `frame`. A
+/// region set up with `xrandr --setmonitor` may have no outputs of its own, so
+/// this goes by position rather than by the monitor's output list.
+fn rotation_of(frame: ScreenRect, crtcs: &[Output]) -> Rotation {
+  crtcs
+    .iter()
+    .find(|crtc| crtc.frame.contains(frame.center()))
+    .map_or(Rotation::Normal, |crtc| crtc.rotation)
+}
+
+/// Connected RandR outputs with an active CRTC. Needs RandR 1.2; the primary
+/// output is only known from 1.3.
+fn randr_crtcs(conn: &xbase::Connection, root: xproto::Window) -> Result<Vec<Output>, Error> {
+  let version = randr_version(conn)?;
+  if version < (1, 2) {
+    return Err(anyhow!("RandR {}.{} is too old", version.0, version.1));
+  }
+
+  let (outputs, timestamp, primary) = if version >= (1, 3) {
+    let srs = trace::sent(
+      xrandr::get_screen_resources_current(conn, root),
+      "RRGetScreenResourcesCurrent",
+      format_args!("{:#x}", root),
+    );
+    let primary = trace::sent(
+      xrandr::get_output_primary(conn, root),
+      "RRGetOutputPrimary",
+      format_args!("{:#x}", root),
+    );
+    let srs = reply!(srs)?;
+    (
+      srs.outputs().to_vec(),
+      srs.config_timestamp(),
+      reply!(primary)?.output(),
+    )
+  } else {
+    let srs = trace::sent(
+      xrandr::get_screen_resources(conn, root),
+      "RRGetScreenResources",
+      format_args!("{:#x}", root),
+    );
+    let srs = reply!(srs)?;
+    (srs.outputs().to_vec(), srs.config_timestamp(), xbase::NONE)
+  };
+
+  let infos = outputs
+    .iter()
+    .map(|o| {
+      (
+        *o,
+        trace::sent(
+          xrandr::get_output_info(conn, *o, timestamp),
+          "RRGetOutputInfo",
+          format_args!("{}", o),
+        ),
+      )
+    })
+    .collect::<Vec<_>>();
+  let infos = infos
+    .into_iter()
+    .filter_map(|(o, cookie)| Some((o, reply!(cookie).ok()?)))
+    .filter(|(_, info)| {
+      info.connection() as u32 == xrandr::CONNECTION_CONNECTED && info.crtc() != xbase::NONE
+    })
+    .collect::<Vec<_>>();
+
+  let crtcs = infos
+    .iter()
+    .map(|(_, info)| {
+      trace::sent(
+        xrandr::get_crtc_info(conn, info.crtc(), timestamp),
+        "RRGetCrtcInfo",
+        format_args!("{}", info.crtc()),
+      )
+    })
+    .collect::<Vec<_>>();
+
+  Ok(
+    infos
+      .iter()
+      .zip(crtcs)
+      .filter_map(|((o, info), crtc)| {
+        let crtc = reply!(crtc).ok()?;
+        Some(Output {
+          name: String::from_utf8_lossy(info.name()).into_owned(),
+          frame: crtc.as_rect(),
+          rotation: Rotation::from_randr(crtc.rotation()),
+          primary: *o == primary,
+          // Filled in by `OutputLayout::set_usable_area`.
+          usable: crtc.as_rect(),
+          configured: false,
+        })
+      })
+      // Some servers report a CRTC with no size rather than no CRTC.
+      .filter(|o| !o.frame.is_empty())
+      .collect(),
+  )
+}
+
+/// Xinerama screens, which have no names, so they're numbered instead. The
+/// first is treated as primary.
+#[cfg(feature = "xinerama")]
+fn xinerama_screens(conn: &xbase::Connection, _: xproto::Window) -> Result<Vec<Output>, Error> {
+  if !extension_present(conn, xinerama::id()) {
+    return Err(anyhow!("Xinerama isn't available"));
+  }
+
+  let active = trace::sent(
+    xinerama::is_active(conn),
+    "XineramaIsActive",
+    format_args!(""),
+  );
+  if reply!(active)?.state() == 0 {
+    return Err(anyhow!("Xinerama isn't active"));
+  }
+
+  let screens = trace::sent(
+    xinerama::query_screens(conn),
+    "XineramaQueryScreens",
+    format_args!(""),
+  );
+  Ok(
+    reply!(screens)?
+      .screen_info()
+      .enumerate()
+      .map(|(i, info)| {
+        let frame = ScreenRect::new(
+          ScreenPoint::new(info.x_org() as i32, info.y_org() as i32),
+          ScreenSize::new(info.width() as i32, info.height() as i32),
+        );
+        Output {
+          name: format!("xinerama-{}", i),
+          frame,
+          rotation: Rotation::Normal,
+          primary: i == 0,
+          usable: frame,
+          configured: false,
+        }
+      })
+      .collect(),
+  )
+}
+
+#[cfg(not(feature = "xinerama"))]
+fn xinerama_screens(_: &xbase::Connection, _: xproto::Window) -> Result<Vec<Output>, Error> {
+  Err(anyhow!("Built without the xinerama feature"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::layout::Rotation;
+
+  fn output(name: &str, frame: ScreenRect, primary: bool) -> Output {
+    Output {
+      name: name.to_owned(),
+      frame,
+      rotation: Rotation::Normal,
+      primary,
+      usable: frame,
+      configured: false,
+    }
+  }
+
+  fn split(columns: &[u32], rows: &[u32], names: &[&str]) -> Split {
+    Split {
+      output: "DP-1".to_owned(),
+      columns: columns.to_vec(),
+      rows: rows.to_vec(),
+      names: names.iter().map(|n| n.to_string()).collect(),
+    }
+  }
+
+  #[test]
+  fn monitors_take_the_rotation_of_the_crtc_under_their_center() {
+    let crtcs = [
+      Output {
+        rotation: Rotation::Left,
+        ..output("DP-1", rect(0, 0, 1440, 2560), false)
+      },
+      output("DP-2", rect(1440, 0, 2560, 1440), false),
+    ];
+    assert_eq!(rotation_of(rect(0, 0, 1440, 2560), &crtcs), Rotation::Left);
+    // The bottom half of the portrait monitor, set up with --setmonitor.
+    assert_eq!(
+      rotation_of(rect(0, 1280, 1440, 1280), &crtcs),
+      Rotation::Left
+    );
+    assert_eq!(
+      rotation_of(rect(1440, 0, 2560, 1440), &crtcs),
+      Rotation::Normal
+    );
+    assert_eq!(
+      rotation_of(rect(5000, 0, 100, 100), &crtcs),
+      Rotation::Normal
+    );
+  }
+
+  #[test]
+  fn spans_add_up_to_the_extent() {
+    assert_eq!(
+      spans(0, 1000, &[1, 1, 1]),
+      [(0, 333), (333, 333), (666, 334)]
+    );
+    assert_eq!(
+      spans(2560, 3440, &[1, 2, 1]),
+      [(2560, 860), (3420, 1720), (5140, 860)]
+    );
+  }
+
+  #[test]
+  fn spans_drop_empty_weights() {
+    assert_eq!(spans(0, 1000, &[1, 0, 1]), [(0, 500), (500, 500)]);
+  }
+
+  #[test]
+  fn split_numbers_regions_left_to_right_then_top_to_bottom() {
+    let regions = split_output(
+      &output("DP-1", rect(0, 0, 2000, 1000), false),
+      &split(&[1, 1], &[1, 1], &[]),
+    );
+    let regions = regions
+      .iter()
+      .map(|o| (o.name.as_str(), o.frame))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      regions,
+      [
+        ("DP-1:1", rect(0, 0, 1000, 500)),
+        ("DP-1:2", rect(1000, 0, 1000, 500)),
+        ("DP-1:3", rect(0, 500, 1000, 500)),
+        ("DP-1:4", rect(1000, 500, 1000, 500)),
+      ]
+    );
+  }
+
+  #[test]
+  fn split_without_rows_is_one_row() {
+    let regions = split_output(
+      &output("DP-1", rect(0, 0, 3440, 1440), true),
+      &split(&[1, 2, 1], &[], &["left", "middle"]),
+    );
+    let regions = regions
+      .iter()
+      .map(|o| (o.name.as_str(), o.frame, o.primary, o.configured))
+      .collect::<Vec<_>>();
+    assert_eq!(
+      regions,
+      [
+        ("left", rect(0, 0, 860, 1440), false, true),
+        ("middle", rect(860, 0, 1720, 1440), true, true),
+        ("DP-1:3", rect(2580, 0, 860, 1440), false, true),
+      ]
+    );
+  }
+}
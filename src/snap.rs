@@ -0,0 +1,120 @@
+//! Magnetic snapping of window edges to the edges of other windows and the
+//! output, for `--snap`.
+
+use crate::{
+  layout::{
+    overlap,
+    Direction,
+  },
+  xcb_util::geometry::*,
+};
+
+/// The closest of `targets` that's within `threshold` of `edge`, which the
+/// command moved there from `original`. Edges that didn't move don't snap, and
+/// targets that aren't ahead of `original` in the direction the edge moved are
+/// ignored, so snapping never undoes or reverses a move.
+fn nearest(edge: i32, original: i32, targets: &[i32], threshold: i32) -> Option<i32> {
+  let moved = (edge - original).signum();
+  if moved == 0 {
+    return None;
+  }
+  targets
+    .iter()
+    .copied()
+    .filter(|t| (t - original) * moved > 0 && (t - edge).abs() <= threshold)
+    .min_by_key(|t| (t - edge).abs())
+}
+
+/// The edges of `others` that `frame`'s sides facing `direction` and its
+/// opposite could line up with: those of rects alongside it.
+fn targets(frame: ScreenRect, others: &[ScreenRect], direction: Direction) -> Vec<i32> {
+  let span = direction.cross_span(frame);
+  others
+    .iter()
+    .filter(|other| overlap(direction.cross_span(**other), span) > 0)
+    .flat_map(|other| {
+      vec![
+        direction.leading_edge(*other),
+        direction.opposite().leading_edge(*other),
+      ]
+    })
+    .collect()
+}
+
+/// Moves the edges of `frame` onto any edges of `others` that are within
+/// `threshold` pixels. `original` is where the window was before; if `frame`
+/// is the same size, it's moved as a whole rather than resized.
+pub fn snap(
+  frame: ScreenRect,
+  original: ScreenRect,
+  others: &[ScreenRect],
+  threshold: i32,
+) -> ScreenRect {
+  if threshold <= 0 {
+    return frame;
+  }
+
+  let moving = frame.size == original.size;
+  let mut snapped = frame;
+  for (low, high) in &[
+    (Direction::West, Direction::East),
+    (Direction::North, Direction::South),
+  ] {
+    let targets = targets(frame, others, *low);
+    let low_edge = low.leading_edge(frame);
+    let high_edge = high.leading_edge(frame);
+    let low_snap = nearest(low_edge, low.leading_edge(original), &targets, threshold);
+    let high_snap = nearest(high_edge, high.leading_edge(original), &targets, threshold);
+
+    if moving {
+      // Whichever side is closer to something wins.
+      let delta = match (low_snap, high_snap) {
+        (Some(l), Some(h)) if (h - high_edge).abs() < (l - low_edge).abs() => h - high_edge,
+        (Some(l), _) => l - low_edge,
+        (None, Some(h)) => h - high_edge,
+        (None, None) => 0,
+      };
+      snapped = snapped.translate(high.offset(delta));
+    } else {
+      if let Some(l) = low_snap {
+        snapped = low.with_leading_edge(snapped, l);
+      }
+      if let Some(h) = high_snap {
+        snapped = high.with_leading_edge(snapped, h);
+      }
+    }
+  }
+  snapped
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn resize_only_snaps_edges_that_moved() {
+    // `grow l`: only the right edge moved, from 300 to 390.
+    let original = rect(100, 100, 200, 200);
+    let frame = rect(100, 100, 290, 200);
+    let others = [rect(95, 95, 100, 100), rect(395, 0, 100, 400)];
+    assert_eq!(snap(frame, original, &others, 10), rect(100, 100, 295, 200));
+  }
+
+  #[test]
+  fn move_never_goes_backwards() {
+    // `nudge l 20`: the left edge went from 100 to 120, and the only nearby
+    // edge is behind where it started.
+    let original = rect(100, 100, 200, 200);
+    let frame = rect(120, 100, 200, 200);
+    let others = [rect(0, 100, 95, 200)];
+    assert_eq!(snap(frame, original, &others, 30), frame);
+  }
+
+  #[test]
+  fn move_snaps_forwards() {
+    let original = rect(100, 100, 200, 200);
+    let frame = rect(120, 100, 200, 200);
+    let others = [rect(0, 100, 125, 200)];
+    assert_eq!(snap(frame, original, &others, 30), rect(125, 100, 200, 200));
+  }
+}
@@ -0,0 +1,609 @@
+use std::{
+  collections::HashMap,
+  fs::File,
+  io::{
+    self,
+    Write,
+  },
+  path::Path,
+};
+
+use anyhow::{
+  anyhow,
+  Error,
+};
+use log::debug;
+use serde::{
+  Deserialize,
+  Serialize,
+};
+use xcb::{
+  base as xbase,
+  xproto,
+};
+
+use crate::{
+  config::Config,
+  error::TileError,
+  layout::{
+    Output,
+    OutputLayout,
+    Strut,
+    UsableArea,
+  },
+  outputs,
+  xcb_util::{
+    connection::ConnectionExt,
+    geometry::*,
+    trace::{
+      self,
+      reply,
+    },
+    window::{
+      SizeHints,
+      ALL_DESKTOPS,
+    },
+  },
+};
+
+/// Enough to cover any property we read; the server truncates to the actual
+/// length.
+const MAX_PROPERTY_LENGTH: u32 = 4096;
+
+/// A desktop given on the command line: a number counting from 0, or `next` or
+/// `prev` to go relative to the current desktop, wrapping around at the ends.
+#[derive(Clone, Copy)]
+pub enum DesktopTarget {
+  Number(u32),
+  Next,
+  Previous,
+}
+
+impl std::str::FromStr for DesktopTarget {
+  type Err = TileError;
+  fn from_str(s: &str) -> Result<DesktopTarget, TileError> {
+    match s {
+      "next" => Ok(DesktopTarget::Next),
+      "prev" => Ok(DesktopTarget::Previous),
+      _ => {
+        u32::from_str(s).map(DesktopTarget::Number).map_err(|_| {
+          TileError::ParseError {
+            arg: s.to_owned(),
+            expected: "a desktop number, next or prev",
+          }
+        })
+      }
+    }
+  }
+}
+
+/// A window given on the command line: an X window id in hex (`0x3a00007`,
+/// as printed by `xprop` and `wmctrl -l`) or decimal, or `active`.
+#[derive(Clone, Copy)]
+pub enum WindowTarget {
+  Id(xproto::Window),
+  Active,
+}
+
+impl std::str::FromStr for WindowTarget {
+  type Err = TileError;
+  fn from_str(s: &str) -> Result<WindowTarget, TileError> {
+    let id = match s {
+      "active" => return Ok(WindowTarget::Active),
+      _ if s.starts_with("0x") => xproto::Window::from_str_radix(&s[2..], 16),
+      _ => xproto::Window::from_str(s),
+    };
+    id.map(WindowTarget::Id).map_err(|_| {
+      TileError::ParseError {
+        arg: s.to_owned(),
+        expected: "a window id like 0x3a00007, or active",
+      }
+    })
+  }
+}
+
+/// A managed client window, as it was when the snapshot was taken.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WindowInfo {
+  pub id: xproto::Window,
+  /// The client area in root window coordinates, excluding decorations.
+  pub frame: ScreenRect,
+  /// The decoration sizes from `_NET_FRAME_EXTENTS`.
+  #[serde(with = "insets_serde")]
+  pub insets: ScreenInsets,
+  /// The atom names in `_NET_WM_STATE`.
+  pub state: Vec<String>,
+  /// The atom names in `_NET_WM_WINDOW_TYPE`, most specific first.
+  #[serde(default)]
+  pub window_type: Vec<String>,
+  /// `_NET_WM_DESKTOP`, which is `ALL_DESKTOPS` for sticky windows.
+  #[serde(default)]
+  pub desktop: Option<u32>,
+  pub hints: SizeHints,
+}
+
+impl WindowInfo {
+  pub fn is_minimised(&self) -> bool { self.state.iter().any(|s| s == "_NET_WM_STATE_HIDDEN") }
+
+  /// Whether this is a panel or the desktop background rather than an
+  /// ordinary window. Some WMs list these in `_NET_CLIENT_LIST`.
+  pub fn is_dock_or_desktop(&self) -> bool {
+    self
+      .window_type
+      .iter()
+      .any(|t| t == "_NET_WM_WINDOW_TYPE_DOCK" || t == "_NET_WM_WINDOW_TYPE_DESKTOP")
+  }
+}
+
+/// Everything the placement commands need to know about the display, captured
+/// in one go.
+///
+/// A snapshot can be written to disk with the `snapshot` command, which gives
+/// bug reports the exact state a command ran against.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+  /// The X screen number; snapshots from before multi-screen support are all
+  /// of screen 0.
+  #[serde(default)]
+  pub screen: usize,
+  pub root: xproto::Window,
+  pub root_frame: ScreenRect,
+  pub layout: OutputLayout,
+  /// `_NET_CLIENT_LIST`, in initial mapping order.
+  pub client_list: Vec<xproto::Window>,
+  /// `_NET_CLIENT_LIST_STACKING`, bottom to top.
+  pub stacking: Vec<xproto::Window>,
+  pub active_window: Option<xproto::Window>,
+  /// `_NET_CURRENT_DESKTOP`.
+  #[serde(default)]
+  pub current_desktop: Option<u32>,
+  /// `_NET_NUMBER_OF_DESKTOPS`.
+  #[serde(default)]
+  pub desktop_count: Option<u32>,
+  /// `_NET_SHOWING_DESKTOP`.
+  #[serde(default)]
+  pub showing_desktop: Option<bool>,
+  /// Every client in `client_list`, plus the active window.
+  pub windows: Vec<WindowInfo>,
+}
+
+struct Atoms {
+  workarea: xproto::Atom,
+  active_window: xproto::Atom,
+  client_list: xproto::Atom,
+  client_list_stacking: xproto::Atom,
+  frame_extents: xproto::Atom,
+  wm_state: xproto::Atom,
+  wm_strut_partial: xproto::Atom,
+  current_desktop: xproto::Atom,
+  number_of_desktops: xproto::Atom,
+  wm_desktop: xproto::Atom,
+  showing_desktop: xproto::Atom,
+  wm_window_type: xproto::Atom,
+}
+
+impl Atoms {
+  fn intern(conn: &xbase::Connection) -> Result<Atoms, Error> {
+    let atoms = conn.get_atoms(&[
+      "_NET_WORKAREA",
+      "_NET_ACTIVE_WINDOW",
+      "_NET_CLIENT_LIST",
+      "_NET_CLIENT_LIST_STACKING",
+      "_NET_FRAME_EXTENTS",
+      "_NET_WM_STATE",
+      "_NET_WM_STRUT_PARTIAL",
+      "_NET_CURRENT_DESKTOP",
+      "_NET_NUMBER_OF_DESKTOPS",
+      "_NET_WM_DESKTOP",
+      "_NET_SHOWING_DESKTOP",
+      "_NET_WM_WINDOW_TYPE",
+    ])?;
+    Ok(Atoms {
+      workarea: atoms[0],
+      active_window: atoms[1],
+      client_list: atoms[2],
+      client_list_stacking: atoms[3],
+      frame_extents: atoms[4],
+      wm_state: atoms[5],
+      wm_strut_partial: atoms[6],
+      current_desktop: atoms[7],
+      number_of_desktops: atoms[8],
+      wm_desktop: atoms[9],
+      showing_desktop: atoms[10],
+      wm_window_type: atoms[11],
+    })
+  }
+}
+
+/// Requests a property, or nothing if `property` is `ATOM_NONE`: atoms are
+/// interned only if they exist, and without an EWMH WM the `_NET_*` ones don't.
+fn get_property<'a>(
+  conn: &'a xbase::Connection,
+  window: xproto::Window,
+  property: xproto::Atom,
+  type_: xproto::Atom,
+) -> Option<xproto::GetPropertyCookie<'a>> {
+  if property == xproto::ATOM_NONE {
+    return None;
+  }
+  Some(trace::sent(
+    xproto::get_property(conn, false, window, property, type_, 0, MAX_PROPERTY_LENGTH),
+    "GetProperty",
+    format_args!("{:#x} {}", window, property),
+  ))
+}
+
+/// The property's value, which is empty if it was never requested.
+fn property_value<T: Clone>(cookie: Option<xproto::GetPropertyCookie>) -> Result<Vec<T>, Error> {
+  match cookie {
+    Some(cookie) => Ok(reply!(cookie)?.value::<T>().to_vec()),
+    None => Ok(vec![]),
+  }
+}
+
+impl Snapshot {
+  /// Captures a snapshot of one X screen. Independent requests are sent
+  /// together before waiting on any of their replies, so this takes a handful
+  /// of round trips regardless of how many outputs or windows there are.
+  pub fn capture(
+    conn: &xbase::Connection,
+    screen: usize,
+    config: &Config,
+  ) -> Result<Snapshot, Error> {
+    let setup = conn.get_setup();
+
+    let root = setup
+      .roots()
+      .nth(screen)
+      .ok_or_else(|| {
+        anyhow!(
+          "There's no screen {} (the display has {})",
+          screen,
+          setup.roots_len()
+        )
+      })?
+      .root();
+
+    let atoms = Atoms::intern(conn)?;
+
+    let root_geometry = trace::sent(
+      xproto::get_geometry(conn, root),
+      "GetGeometry",
+      format_args!("{:#x}", root),
+    );
+    let workarea = get_property(conn, root, atoms.workarea, xproto::ATOM_CARDINAL);
+    let active_window = get_property(conn, root, atoms.active_window, xproto::ATOM_WINDOW);
+    let current_desktop = get_property(conn, root, atoms.current_desktop, xproto::ATOM_CARDINAL);
+    let desktop_count = get_property(conn, root, atoms.number_of_desktops, xproto::ATOM_CARDINAL);
+    let showing_desktop = get_property(conn, root, atoms.showing_desktop, xproto::ATOM_CARDINAL);
+    let client_list = get_property(conn, root, atoms.client_list, xproto::ATOM_WINDOW);
+    let stacking = get_property(conn, root, atoms.client_list_stacking, xproto::ATOM_WINDOW);
+
+    let root_frame = reply!(root_geometry)?.as_rect();
+    let work_areas = property_value::<u32>(workarea)?
+      .chunks_exact(4)
+      .map(|slc| {
+        ScreenRect::new(
+          ScreenPoint::new(slc[0] as i32, slc[1] as i32),
+          ScreenSize::new(slc[2] as i32, slc[3] as i32),
+        )
+      })
+      .collect::<Vec<ScreenRect>>();
+    let active_window = property_value::<xproto::Window>(active_window)?
+      .first()
+      .copied()
+      .filter(|w| *w != xbase::NONE);
+    let client_list = property_value::<xproto::Window>(client_list)?;
+    let stacking = property_value::<xproto::Window>(stacking)?;
+    let current_desktop = property_value::<u32>(current_desktop)?.first().copied();
+    let desktop_count = property_value::<u32>(desktop_count)?.first().copied();
+    let showing_desktop = property_value::<u32>(showing_desktop)?
+      .first()
+      .map(|showing| *showing != 0);
+
+    debug!("Work areas: {:?}", work_areas);
+
+    let (source, outputs) = outputs::discover(conn, root, root_frame)?;
+    let outputs = outputs::apply_config(outputs, config);
+    let struts = Snapshot::capture_struts(conn, &atoms, root, root_frame)?;
+    let layout = OutputLayout::new(source, outputs, work_areas, struts);
+
+    let mut ids = client_list.clone();
+    if let Some(active_window) = active_window {
+      if !ids.contains(&active_window) {
+        ids.push(active_window);
+      }
+    }
+    let windows = Snapshot::capture_windows(conn, &atoms, root, &ids)?;
+
+    Ok(Snapshot {
+      screen,
+      root,
+      root_frame,
+      layout,
+      client_list,
+      stacking,
+      active_window,
+      current_desktop,
+      desktop_count,
+      showing_desktop,
+      windows,
+    })
+  }
+
+  /// Walks the window tree below `root` a level at a time, collecting every
+  /// strut.
+  fn capture_struts(
+    conn: &xbase::Connection,
+    atoms: &Atoms,
+    root: xproto::Window,
+    root_frame: ScreenRect,
+  ) -> Result<Vec<Strut>, Error> {
+    let mut struts = vec![];
+    let mut level = vec![root];
+    while !level.is_empty() {
+      let trees = level
+        .iter()
+        .map(|w| {
+          trace::sent(
+            xproto::query_tree(conn, *w),
+            "QueryTree",
+            format_args!("{:#x}", w),
+          )
+        })
+        .collect::<Vec<_>>();
+      let values = level
+        .iter()
+        .map(|w| get_property(conn, *w, atoms.wm_strut_partial, xproto::ATOM_CARDINAL))
+        .collect::<Vec<_>>();
+
+      for (w, value) in level.iter().zip(values) {
+        // Windows may be destroyed while we walk the tree; skip them.
+        let value = match property_value::<u32>(value) {
+          Ok(value) => value,
+          Err(_) => continue,
+        };
+        for strut in Strut::from_partial(&value, root_frame) {
+          debug!("Found strut on window {}: {:?}", w, strut);
+          struts.push(strut);
+        }
+      }
+
+      level = trees
+        .into_iter()
+        .filter_map(|cookie| reply!(cookie).ok())
+        .flat_map(|tree| tree.children().to_vec())
+        .collect();
+    }
+
+    Ok(struts)
+  }
+
+  fn capture_windows(
+    conn: &xbase::Connection,
+    atoms: &Atoms,
+    root: xproto::Window,
+    ids: &[xproto::Window],
+  ) -> Result<Vec<WindowInfo>, Error> {
+    let cookies = ids
+      .iter()
+      .map(|w| {
+        (
+          *w,
+          trace::sent(
+            xproto::get_geometry(conn, *w),
+            "GetGeometry",
+            format_args!("{:#x}", w),
+          ),
+          trace::sent(
+            xproto::translate_coordinates(conn, *w, root, 0, 0),
+            "TranslateCoordinates",
+            format_args!("{:#x} {:#x}", w, root),
+          ),
+          get_property(conn, *w, atoms.frame_extents, xproto::ATOM_CARDINAL),
+          get_property(conn, *w, atoms.wm_state, xproto::ATOM_ATOM),
+          get_property(
+            conn,
+            *w,
+            xproto::ATOM_WM_NORMAL_HINTS,
+            xproto::ATOM_WM_SIZE_HINTS,
+          ),
+          get_property(conn, *w, atoms.wm_desktop, xproto::ATOM_CARDINAL),
+          get_property(conn, *w, atoms.wm_window_type, xproto::ATOM_ATOM),
+        )
+      })
+      .collect::<Vec<_>>();
+
+    let mut windows = vec![];
+    let mut state_atoms = vec![];
+    let mut type_atoms = vec![];
+    for (id, geometry, translated, insets, state, hints, desktop, window_type) in cookies {
+      // As with struts, windows that have gone away since the client list was
+      // read are skipped.
+      let (mut frame, translated) = match (reply!(geometry), reply!(translated)) {
+        (Ok(geometry), Ok(translated)) => (geometry.as_rect(), translated),
+        _ => continue,
+      };
+      frame.origin.x = translated.dst_x() as i32;
+      frame.origin.y = translated.dst_y() as i32;
+
+      let insets = property_value::<u32>(insets).unwrap_or_default();
+      let insets = if let [left, right, top, bottom] = insets.as_slice() {
+        ScreenInsets::new(*top as i32, *right as i32, *bottom as i32, *left as i32)
+      } else {
+        ScreenInsets::zero()
+      };
+
+      let state = property_value::<xproto::Atom>(state).unwrap_or_default();
+      let hints =
+        SizeHints::from_wm_normal_hints(&property_value::<u32>(hints).unwrap_or_default());
+      let desktop = property_value::<u32>(desktop)
+        .ok()
+        .and_then(|d| d.first().copied());
+      let window_type = property_value::<xproto::Atom>(window_type).unwrap_or_default();
+
+      windows.push(WindowInfo {
+        id,
+        frame,
+        insets,
+        state: vec![],
+        window_type: vec![],
+        desktop,
+        hints,
+      });
+      state_atoms.push(state);
+      type_atoms.push(window_type);
+    }
+
+    let mut unique = [state_atoms.concat(), type_atoms.concat()].concat();
+    unique.sort_unstable();
+    unique.dedup();
+    let names = unique
+      .iter()
+      .copied()
+      .zip(conn.get_atom_names(&unique)?)
+      .collect::<HashMap<_, _>>();
+
+    for ((window, state), window_type) in windows.iter_mut().zip(state_atoms).zip(type_atoms) {
+      window.state = state.iter().map(|atom| names[atom].clone()).collect();
+      window.window_type = window_type.iter().map(|atom| names[atom].clone()).collect();
+    }
+
+    Ok(windows)
+  }
+
+  pub fn window(&self, id: xproto::Window) -> Option<&WindowInfo> {
+    self.windows.iter().find(|w| w.id == id)
+  }
+
+  /// The output `window` is on, or the one it overlaps the most.
+  pub fn output_of(&self, window: &WindowInfo) -> Result<&Output, TileError> {
+    self
+      .layout
+      .output_for(window.frame)
+      .ok_or(TileError::NoOutputForWindow { window: window.id })
+  }
+
+  pub fn set_usable_area(&mut self, strategy: UsableArea) {
+    self.layout.set_usable_area(strategy, self.current_desktop);
+  }
+
+  pub fn on_current_desktop(&self, window: &WindowInfo) -> bool {
+    match (window.desktop, self.current_desktop) {
+      (Some(desktop), Some(current)) => desktop == current || desktop == ALL_DESKTOPS,
+      _ => true,
+    }
+  }
+
+  /// Every window on the current desktop that isn't minimised, leaving out
+  /// docks and the desktop window.
+  pub fn visible_windows(&self) -> impl Iterator<Item = &WindowInfo> {
+    self
+      .windows
+      .iter()
+      .filter(move |w| self.on_current_desktop(w) && !w.is_minimised() && !w.is_dock_or_desktop())
+  }
+
+  /// The minimised windows on the current desktop, from the top of the stack
+  /// down.
+  pub fn minimised_windows(&self) -> impl Iterator<Item = &WindowInfo> {
+    self
+      .stacking
+      .iter()
+      .rev()
+      .filter_map(move |id| self.window(*id))
+      .filter(move |w| self.on_current_desktop(w) && w.is_minimised())
+  }
+
+  /// Resolves a desktop number, or next or previous from the current one, to a
+  /// desktop that exists.
+  pub fn desktop(&self, target: DesktopTarget) -> Result<u32, Error> {
+    let count = self.desktop_count.ok_or(TileError::WmUnsupported {
+      atom: "_NET_NUMBER_OF_DESKTOPS",
+    })?;
+    let current = || {
+      self.current_desktop.ok_or(TileError::WmUnsupported {
+        atom: "_NET_CURRENT_DESKTOP",
+      })
+    };
+    match target {
+      DesktopTarget::Number(n) if n < count => Ok(n),
+      DesktopTarget::Number(n) => Err(anyhow!("There's no desktop {} (there are {})", n, count)),
+      DesktopTarget::Next => Ok((current()? + 1) % count),
+      DesktopTarget::Previous => Ok((current()? + count - 1) % count),
+    }
+  }
+
+  /// Looks up a window given on the command line, which has to be one the WM
+  /// manages.
+  pub fn find(&self, target: WindowTarget) -> Result<&WindowInfo, Error> {
+    match target {
+      WindowTarget::Active => self.active(),
+      WindowTarget::Id(id) => {
+        self
+          .window(id)
+          .ok_or_else(|| anyhow!("{:#x} isn't a window the window manager knows about", id))
+      }
+    }
+  }
+
+  pub fn active(&self) -> Result<&WindowInfo, Error> {
+    self
+      .active_window
+      .and_then(|id| self.window(id))
+      .ok_or_else(|| TileError::NoActiveWindow.into())
+  }
+
+  /// Reads a snapshot previously written by `save`.
+  pub fn load(path: &Path) -> Result<Snapshot, Error> {
+    let file =
+      File::open(path).map_err(|e| anyhow!("Couldn't open snapshot {}: {}", path.display(), e))?;
+    Ok(serde_json::from_reader(io::BufReader::new(file))?)
+  }
+
+  /// Writes the snapshot as JSON to `path`, or to stdout if there isn't one.
+  pub fn save(&self, path: Option<&Path>) -> Result<(), Error> {
+    let mut out: Box<dyn Write> = match path {
+      Some(path) => Box::new(File::create(path)?),
+      None => Box::new(io::stdout()),
+    };
+    serde_json::to_writer_pretty(&mut out, self)?;
+    writeln!(out)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn window(id: xproto::Window, window_type: &str, state: &str) -> String {
+    format!(
+      r#"{{"id": {}, "frame": {{"origin": [0, 0], "size": [800, 600]}}, "insets": {{"top": 0, "right": 0, "bottom": 0, "left": 0}}, "state": [{}], "window_type": ["{}"], "hints": {{"min_size": null, "max_size": null, "base_size": null, "resize_inc": null}}}}"#,
+      id, state, window_type
+    )
+  }
+
+  #[test]
+  fn missing_atoms_read_as_empty_properties() {
+    assert!(property_value::<u32>(None).unwrap().is_empty());
+  }
+
+  #[test]
+  fn visible_windows_leave_out_docks_and_the_desktop() {
+    let windows = [
+      window(1, "_NET_WM_WINDOW_TYPE_DESKTOP", ""),
+      window(2, "_NET_WM_WINDOW_TYPE_DOCK", ""),
+      window(3, "_NET_WM_WINDOW_TYPE_NORMAL", ""),
+      window(4, "_NET_WM_WINDOW_TYPE_NORMAL", r#""_NET_WM_STATE_HIDDEN""#),
+      window(5, "_NET_WM_WINDOW_TYPE_DIALOG", ""),
+    ];
+    let snapshot: Snapshot = serde_json::from_str(&format!(
+      r#"{{"root": 1, "root_frame": {{"origin": [0, 0], "size": [1920, 1080]}}, "layout": {{"outputs": [], "work_areas": [], "struts": []}}, "client_list": [1, 2, 3, 4, 5], "stacking": [1, 2, 3, 4, 5], "active_window": 3, "windows": [{}]}}"#,
+      windows.join(", ")
+    ))
+    .unwrap();
+    let visible = snapshot.visible_windows().map(|w| w.id).collect::<Vec<_>>();
+    assert_eq!(visible, [3, 5]);
+  }
+}
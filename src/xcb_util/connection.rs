@@ -5,12 +5,62 @@ use xcb::{
 
 use anyhow::Error;
 
+use crate::xcb_util::trace::{
+  self,
+  reply,
+};
+
 pub trait ConnectionExt {
   fn get_atom(&self, atom_: &str) -> Result<xproto::Atom, Error>;
+
+  /// Interns several atoms with a single round trip.
+  fn get_atoms(&self, atoms: &[&str]) -> Result<Vec<xproto::Atom>, Error>;
+
+  /// Looks up the names of several atoms with a single round trip.
+  fn get_atom_names(&self, atoms: &[xproto::Atom]) -> Result<Vec<String>, Error>;
 }
 
 impl ConnectionExt for xbase::Connection {
   fn get_atom(&self, atom_: &str) -> Result<xproto::Atom, Error> {
-    Ok(xproto::intern_atom(&self, true, atom_).get_reply()?.atom())
+    let cookie = trace::sent(
+      xproto::intern_atom(self, true, atom_),
+      "InternAtom",
+      format_args!("{}", atom_),
+    );
+    Ok(reply!(cookie)?.atom())
+  }
+
+  fn get_atoms(&self, atoms: &[&str]) -> Result<Vec<xproto::Atom>, Error> {
+    let cookies = atoms
+      .iter()
+      .map(|atom| {
+        trace::sent(
+          xproto::intern_atom(self, true, atom),
+          "InternAtom",
+          format_args!("{}", atom),
+        )
+      })
+      .collect::<Vec<_>>();
+    cookies
+      .into_iter()
+      .map(|cookie| Ok(reply!(cookie)?.atom()))
+      .collect()
+  }
+
+  fn get_atom_names(&self, atoms: &[xproto::Atom]) -> Result<Vec<String>, Error> {
+    let cookies = atoms
+      .iter()
+      .map(|atom| {
+        trace::sent(
+          xproto::get_atom_name(self, *atom),
+          "GetAtomName",
+          format_args!("{}", atom),
+        )
+      })
+      .collect::<Vec<_>>();
+    cookies
+      .into_iter()
+      .map(|cookie| Ok(reply!(cookie)?.name().to_owned()))
+      .collect()
   }
 }
@@ -10,6 +10,12 @@ pub type ScreenSize = Size2D<i32, ScreenSpace>;
 pub type ScreenRect = Rect<i32, ScreenSpace>;
 pub type ScreenInsets = SideOffsets2D<i32, ScreenSpace>;
 
+/// Shorthand for writing out rects in tests.
+#[cfg(test)]
+pub fn rect(x: i32, y: i32, w: i32, h: i32) -> ScreenRect {
+  ScreenRect::new(ScreenPoint::new(x, y), ScreenSize::new(w, h))
+}
+
 pub struct DisplayPercentageSpace;
 pub type DisplayPercentageSpacePoint = Point2D<f32, DisplayPercentageSpace>;
 pub type DisplayPercentageSpaceSize = Size2D<f32, DisplayPercentageSpace>;
@@ -77,3 +83,67 @@ impl ToScreenRect for DisplayPercentageSpaceRect {
     )
   }
 }
+
+pub trait ClampInto {
+  fn clamp_into(&self, bounds: ScreenRect) -> Self;
+}
+
+impl ClampInto for ScreenRect {
+  /// Shrinks the rect to fit in `bounds` if it's too big, then moves it as
+  /// little as possible to be inside them.
+  fn clamp_into(&self, bounds: ScreenRect) -> ScreenRect {
+    let size = self.size.min(bounds.size);
+    let origin = ScreenPoint::new(
+      self
+        .origin
+        .x
+        .clamp(bounds.min_x(), bounds.max_x() - size.width),
+      self
+        .origin
+        .y
+        .clamp(bounds.min_y(), bounds.max_y() - size.height),
+    );
+    ScreenRect::new(origin, size)
+  }
+}
+
+/// (De)serializes `ScreenInsets` without euclid's unit marker field, for use
+/// with `#[serde(with = "insets_serde")]`.
+pub mod insets_serde {
+  use serde::{
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+  };
+
+  use super::ScreenInsets;
+
+  #[derive(Serialize, Deserialize)]
+  struct Insets {
+    top: i32,
+    right: i32,
+    bottom: i32,
+    left: i32,
+  }
+
+  pub fn serialize<S: Serializer>(insets: &ScreenInsets, serializer: S) -> Result<S::Ok, S::Error> {
+    Insets {
+      top: insets.top,
+      right: insets.right,
+      bottom: insets.bottom,
+      left: insets.left,
+    }
+    .serialize(serializer)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ScreenInsets, D::Error> {
+    let insets = Insets::deserialize(deserializer)?;
+    Ok(ScreenInsets::new(
+      insets.top,
+      insets.right,
+      insets.bottom,
+      insets.left,
+    ))
+  }
+}
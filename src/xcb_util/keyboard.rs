@@ -0,0 +1,272 @@
+//! Reading keys while the keyboard is grabbed, for the interactive commands,
+//! and optionally the pointer as well.
+
+use std::{
+  thread,
+  time::{
+    Duration,
+    Instant,
+  },
+};
+
+use anyhow::{
+  anyhow,
+  Error,
+};
+use log::debug;
+use xcb::{
+  base as xbase,
+  xproto,
+};
+
+use crate::{
+  layout::Direction,
+  xcb_util::{
+    geometry::*,
+    trace::{
+      self,
+      reply,
+    },
+  },
+};
+
+/// The keysyms the interactive commands look for.
+pub mod keysym {
+  pub const RETURN: u32 = 0xff0d;
+  pub const KP_ENTER: u32 = 0xff8d;
+  pub const ESCAPE: u32 = 0xff1b;
+  pub const BACKSPACE: u32 = 0xff08;
+  pub const LEFT: u32 = 0xff51;
+  pub const UP: u32 = 0xff52;
+  pub const RIGHT: u32 = 0xff53;
+  pub const DOWN: u32 = 0xff54;
+}
+
+/// A key press. `keysym` ignores modifiers, so shift-h is `h` with `shift` set.
+#[derive(Clone, Copy, Debug)]
+pub struct Key {
+  pub keysym: u32,
+  pub shift: bool,
+}
+
+impl Key {
+  /// The key's character, for keys that have a printable ASCII one.
+  pub fn char(self) -> Option<char> {
+    match self.keysym {
+      0x20..=0x7e => Some(self.keysym as u8 as char),
+      _ => None,
+    }
+  }
+
+  pub fn is_confirm(self) -> bool { matches!(self.keysym, keysym::RETURN | keysym::KP_ENTER) }
+
+  pub fn is_cancel(self) -> bool { self.keysym == keysym::ESCAPE }
+
+  /// The direction for hjkl (with or without shift) and the arrow keys.
+  pub fn direction(self) -> Option<Direction> {
+    match (self.keysym, self.char().map(|c| c.to_ascii_lowercase())) {
+      (keysym::LEFT, _) | (_, Some('h')) => Some(Direction::West),
+      (keysym::DOWN, _) | (_, Some('j')) => Some(Direction::South),
+      (keysym::UP, _) | (_, Some('k')) => Some(Direction::North),
+      (keysym::RIGHT, _) | (_, Some('l')) => Some(Direction::East),
+      _ => None,
+    }
+  }
+}
+
+/// Something the user did while input was grabbed. Pointer positions are in
+/// root window coordinates, and only left button presses are reported.
+#[derive(Clone, Copy, Debug)]
+pub enum Input {
+  Key(Key),
+  Press(ScreenPoint),
+  Motion(ScreenPoint),
+  Release(ScreenPoint),
+}
+
+/// An active keyboard grab, released when dropped.
+pub struct KeyboardGrab<'a> {
+  conn: &'a xbase::Connection,
+  min_keycode: xproto::Keycode,
+  keysyms_per_keycode: usize,
+  keysyms: Vec<xproto::Keysym>,
+  pointer: bool,
+}
+
+impl<'a> KeyboardGrab<'a> {
+  /// Grabs the keyboard. The key binding that started x11-tile may still be
+  /// held, and grabbed by whatever runs the binding, so this retries for a
+  /// little while before giving up.
+  pub fn new(conn: &'a xbase::Connection, root: xproto::Window) -> Result<KeyboardGrab<'a>, Error> {
+    let setup = conn.get_setup();
+    let min_keycode = setup.min_keycode();
+    let count = setup.max_keycode() - min_keycode + 1;
+    let mapping = reply!(trace::sent(
+      xproto::get_keyboard_mapping(conn, min_keycode, count),
+      "GetKeyboardMapping",
+      format_args!("{} {}", min_keycode, count),
+    ))?;
+
+    let mut status = xproto::GRAB_STATUS_ALREADY_GRABBED as u8;
+    for _ in 0..20 {
+      let grab = reply!(trace::sent(
+        xproto::grab_keyboard(
+          conn,
+          false,
+          root,
+          xbase::CURRENT_TIME,
+          xproto::GRAB_MODE_ASYNC as u8,
+          xproto::GRAB_MODE_ASYNC as u8,
+        ),
+        "GrabKeyboard",
+        format_args!("{:#x}", root),
+      ))?;
+      status = grab.status();
+      if status == xproto::GRAB_STATUS_SUCCESS as u8 {
+        break;
+      }
+      thread::sleep(Duration::from_millis(25));
+    }
+    if status != xproto::GRAB_STATUS_SUCCESS as u8 {
+      return Err(anyhow!(
+        "Couldn't grab the keyboard (status {}); another program is holding it",
+        status
+      ));
+    }
+
+    Ok(KeyboardGrab {
+      conn,
+      min_keycode,
+      keysyms_per_keycode: mapping.keysyms_per_keycode() as usize,
+      keysyms: mapping.keysyms().to_vec(),
+      pointer: false,
+    })
+  }
+
+  /// Grabs the pointer as well, so that `next_input` reports button presses
+  /// and motion anywhere on the screen.
+  pub fn grab_pointer(&mut self, root: xproto::Window) -> Result<(), Error> {
+    let mask = xproto::EVENT_MASK_BUTTON_PRESS
+      | xproto::EVENT_MASK_BUTTON_RELEASE
+      | xproto::EVENT_MASK_POINTER_MOTION;
+    let grab = reply!(trace::sent(
+      xproto::grab_pointer(
+        self.conn,
+        false,
+        root,
+        mask as u16,
+        xproto::GRAB_MODE_ASYNC as u8,
+        xproto::GRAB_MODE_ASYNC as u8,
+        xbase::NONE,
+        xbase::NONE,
+        xbase::CURRENT_TIME,
+      ),
+      "GrabPointer",
+      format_args!("{:#x}", root),
+    ))?;
+    if grab.status() != xproto::GRAB_STATUS_SUCCESS as u8 {
+      return Err(anyhow!(
+        "Couldn't grab the pointer (status {}); another program is holding it",
+        grab.status()
+      ));
+    }
+    self.pointer = true;
+    Ok(())
+  }
+
+  /// Waits for the next key press, ignoring the pointer.
+  pub fn next_key(&self) -> Result<Key, Error> {
+    loop {
+      if let Input::Key(key) = self.next_input()? {
+        return Ok(key);
+      }
+    }
+  }
+
+  /// Waits for the next key press, or pointer event if the pointer is grabbed.
+  pub fn next_input(&self) -> Result<Input, Error> {
+    loop {
+      let event = self
+        .conn
+        .wait_for_event()
+        .ok_or_else(|| anyhow!("Lost the connection to the X server"))?;
+      if let Some(input) = self.decode(&event) {
+        return Ok(input);
+      }
+    }
+  }
+
+  /// Like `next_input`, but gives up after `timeout`.
+  pub fn next_input_within(&self, timeout: Duration) -> Result<Option<Input>, Error> {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+      match self.conn.poll_for_event() {
+        Some(event) => {
+          if let Some(input) = self.decode(&event) {
+            return Ok(Some(input));
+          }
+        }
+        None => {
+          if let Err(e) = self.conn.has_error() {
+            return Err(anyhow!("Lost the connection to the X server: {:?}", e));
+          }
+          thread::sleep(Duration::from_millis(10));
+        }
+      }
+    }
+    Ok(None)
+  }
+
+  fn decode(&self, event: &xbase::GenericEvent) -> Option<Input> {
+    match event.response_type() & !0x80 {
+      xproto::KEY_PRESS => {
+        let press: &xproto::KeyPressEvent = unsafe { xbase::cast_event(event) };
+        let index = (press.detail() - self.min_keycode) as usize * self.keysyms_per_keycode;
+        let key = Key {
+          keysym: self.keysyms.get(index).copied().unwrap_or(0),
+          shift: press.state() & xproto::MOD_MASK_SHIFT as u16 != 0,
+        };
+        debug!(keysym = key.keysym, shift = key.shift; "Key pressed");
+        Some(Input::Key(key))
+      }
+      kind @ (xproto::BUTTON_PRESS | xproto::BUTTON_RELEASE) => {
+        let button: &xproto::ButtonPressEvent = unsafe { xbase::cast_event(event) };
+        if button.detail() != xproto::BUTTON_INDEX_1 as u8 {
+          return None;
+        }
+        let point = ScreenPoint::new(button.root_x() as i32, button.root_y() as i32);
+        Some(if kind == xproto::BUTTON_PRESS {
+          Input::Press(point)
+        } else {
+          Input::Release(point)
+        })
+      }
+      xproto::MOTION_NOTIFY => {
+        let motion: &xproto::MotionNotifyEvent = unsafe { xbase::cast_event(event) };
+        Some(Input::Motion(ScreenPoint::new(
+          motion.root_x() as i32,
+          motion.root_y() as i32,
+        )))
+      }
+      _ => None,
+    }
+  }
+}
+
+impl Drop for KeyboardGrab<'_> {
+  fn drop(&mut self) {
+    trace::sent(
+      xproto::ungrab_keyboard(self.conn, xbase::CURRENT_TIME),
+      "UngrabKeyboard",
+      format_args!(""),
+    );
+    if self.pointer {
+      trace::sent(
+        xproto::ungrab_pointer(self.conn, xbase::CURRENT_TIME),
+        "UngrabPointer",
+        format_args!(""),
+      );
+    }
+    self.conn.flush();
+  }
+}
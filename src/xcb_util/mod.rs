@@ -1,3 +1,7 @@
 pub mod connection;
 pub mod geometry;
+pub mod keyboard;
+pub mod overlay;
+pub mod randr_monitors;
+pub mod trace;
 pub mod window;
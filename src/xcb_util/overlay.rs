@@ -0,0 +1,362 @@
+//! Override-redirect windows shown on top of everything else, for commands
+//! that show the user something before acting on it. They're drawn with core X
+//! requests only, so they work without a compositor.
+
+use anyhow::{
+  anyhow,
+  Error,
+};
+use xcb::{
+  base::{
+    self as xbase,
+    CookieSeq,
+  },
+  xproto,
+};
+
+use crate::xcb_util::{
+  geometry::*,
+  trace::{
+    self,
+    reply,
+  },
+};
+
+/// The colour of outlines, as a TrueColor pixel.
+pub const HIGHLIGHT: u32 = 0x3d_ae_e9;
+
+/// The colour of grid lines.
+pub const GRID_LINE: u32 = 0x80_80_80;
+
+/// The colour of label text.
+pub const LABEL_TEXT: u32 = 0x00_00_00;
+
+/// The fonts labels are drawn in, in order of preference. Every X server has
+/// `fixed`.
+const LABEL_FONTS: &[&str] = &["-misc-fixed-bold-r-normal--18-*-*-*-*-*-iso8859-1", "fixed"];
+
+/// The space around label text, in pixels.
+const LABEL_PADDING: i32 = 6;
+
+/// How wide the sides of an outline are, in pixels.
+const OUTLINE_WIDTH: i32 = 4;
+
+/// Creates an unmapped override-redirect window filled with `pixel`, which the
+/// WM won't manage or decorate.
+pub fn create_window(conn: &xbase::Connection, root: xproto::Window, pixel: u32) -> xproto::Window {
+  create_window_with(conn, root, (xproto::CW_BACK_PIXEL, pixel))
+}
+
+/// Like `create_window`, with the background given as a `CW_BACK_*` value.
+fn create_window_with(
+  conn: &xbase::Connection,
+  root: xproto::Window,
+  background: (u32, u32),
+) -> xproto::Window {
+  let window = conn.generate_id();
+  trace::sent(
+    xproto::create_window(
+      conn,
+      xbase::COPY_FROM_PARENT as u8,
+      window,
+      root,
+      0,
+      0,
+      1,
+      1,
+      0,
+      xproto::WINDOW_CLASS_INPUT_OUTPUT as u16,
+      xbase::COPY_FROM_PARENT,
+      &[background, (xproto::CW_OVERRIDE_REDIRECT, 1)],
+    ),
+    "CreateWindow",
+    format_args!("{:#x}", window),
+  );
+  window
+}
+
+/// Moves `window` to `rect`, maps it and puts it above every other window.
+pub fn show_window(conn: &xbase::Connection, window: xproto::Window, rect: ScreenRect) {
+  trace::sent(
+    xproto::configure_window(
+      conn,
+      window,
+      &[
+        (xproto::CONFIG_WINDOW_X as u16, rect.origin.x as u32),
+        (xproto::CONFIG_WINDOW_Y as u16, rect.origin.y as u32),
+        (
+          xproto::CONFIG_WINDOW_WIDTH as u16,
+          rect.size.width.max(1) as u32,
+        ),
+        (
+          xproto::CONFIG_WINDOW_HEIGHT as u16,
+          rect.size.height.max(1) as u32,
+        ),
+        (
+          xproto::CONFIG_WINDOW_STACK_MODE as u16,
+          xproto::STACK_MODE_ABOVE,
+        ),
+      ],
+    ),
+    "ConfigureWindow",
+    format_args!("{:#x} {}", window, rect),
+  );
+  trace::sent(
+    xproto::map_window(conn, window),
+    "MapWindow",
+    format_args!("{:#x}", window),
+  );
+}
+
+pub fn destroy_window(conn: &xbase::Connection, window: xproto::Window) {
+  trace::sent(
+    xproto::destroy_window(conn, window),
+    "DestroyWindow",
+    format_args!("{:#x}", window),
+  );
+}
+
+/// Filled rectangles on screen, removed when dropped.
+pub struct Rects<'a> {
+  conn: &'a xbase::Connection,
+  windows: Vec<xproto::Window>,
+}
+
+impl<'a> Rects<'a> {
+  pub fn show(
+    conn: &'a xbase::Connection,
+    root: xproto::Window,
+    rects: &[ScreenRect],
+    pixel: u32,
+  ) -> Rects<'a> {
+    let windows = rects
+      .iter()
+      .map(|rect| {
+        let window = create_window(conn, root, pixel);
+        show_window(conn, window, *rect);
+        window
+      })
+      .collect();
+    conn.flush();
+    Rects { conn, windows }
+  }
+}
+
+impl Drop for Rects<'_> {
+  fn drop(&mut self) {
+    for window in &self.windows {
+      destroy_window(self.conn, *window);
+    }
+    self.conn.flush();
+  }
+}
+
+/// A hollow rectangle on screen, made of a thin window for each side. It's
+/// removed when dropped.
+pub struct Outline<'a> {
+  conn: &'a xbase::Connection,
+  sides: Vec<xproto::Window>,
+}
+
+impl<'a> Outline<'a> {
+  pub fn new(conn: &'a xbase::Connection, root: xproto::Window) -> Outline<'a> {
+    let sides = (0..4)
+      .map(|_| create_window(conn, root, HIGHLIGHT))
+      .collect();
+    Outline { conn, sides }
+  }
+
+  /// Moves the outline so that it traces the inside of `rect`.
+  pub fn show(&self, rect: ScreenRect) {
+    let width = OUTLINE_WIDTH.min(rect.size.width).min(rect.size.height);
+    let sides = [
+      ScreenRect::new(rect.origin, ScreenSize::new(rect.size.width, width)),
+      ScreenRect::new(
+        ScreenPoint::new(rect.min_x(), rect.max_y() - width),
+        ScreenSize::new(rect.size.width, width),
+      ),
+      ScreenRect::new(rect.origin, ScreenSize::new(width, rect.size.height)),
+      ScreenRect::new(
+        ScreenPoint::new(rect.max_x() - width, rect.min_y()),
+        ScreenSize::new(width, rect.size.height),
+      ),
+    ];
+    for (window, side) in self.sides.iter().zip(&sides) {
+      show_window(self.conn, *window, *side);
+    }
+    self.conn.flush();
+  }
+}
+
+impl Drop for Outline<'_> {
+  fn drop(&mut self) {
+    for window in &self.sides {
+      destroy_window(self.conn, *window);
+    }
+    self.conn.flush();
+  }
+}
+
+/// Opens the first of `LABEL_FONTS` that the server has.
+fn open_label_font(conn: &xbase::Connection) -> Result<xproto::Font, Error> {
+  for name in LABEL_FONTS {
+    let font = conn.generate_id();
+    let cookie = trace::sent(
+      xproto::open_font_checked(conn, font, name),
+      "OpenFont",
+      format_args!("{:#x} {}", font, name),
+    );
+    let seq = cookie.cookie.sequence();
+    if trace::received(seq, cookie.request_check()).is_ok() {
+      return Ok(font);
+    }
+  }
+  Err(anyhow!("Couldn't open any of the fonts {:?}", LABEL_FONTS))
+}
+
+/// Short pieces of text on screen, each in its own box, removed when dropped.
+///
+/// Each label is drawn once into a pixmap that becomes its window's
+/// background, so the server redraws labels by itself when they're uncovered.
+pub struct Labels<'a> {
+  conn: &'a xbase::Connection,
+  windows: Vec<xproto::Window>,
+  pixmaps: Vec<xproto::Pixmap>,
+}
+
+impl<'a> Labels<'a> {
+  /// Shows each label's text centred on its point.
+  pub fn show(
+    conn: &'a xbase::Connection,
+    root: xproto::Window,
+    labels: &[(ScreenPoint, String)],
+  ) -> Result<Labels<'a>, Error> {
+    let depth = conn
+      .get_setup()
+      .roots()
+      .find(|screen| screen.root() == root)
+      .map(|screen| screen.root_depth())
+      .ok_or_else(|| anyhow!("{:#x} isn't a root window", root))?;
+
+    let font = open_label_font(conn)?;
+    let metrics = reply!(trace::sent(
+      xproto::query_font(conn, font),
+      "QueryFont",
+      format_args!("{:#x}", font),
+    ))?;
+    let ascent = metrics.font_ascent() as i32;
+    let height = ascent + metrics.font_descent() as i32 + 2 * LABEL_PADDING;
+    let char_width = metrics.max_bounds().character_width() as i32;
+
+    let fill = conn.generate_id();
+    trace::sent(
+      xproto::create_gc(conn, fill, root, &[(xproto::GC_FOREGROUND, HIGHLIGHT)]),
+      "CreateGC",
+      format_args!("{:#x}", fill),
+    );
+    let text = conn.generate_id();
+    trace::sent(
+      xproto::create_gc(
+        conn,
+        text,
+        root,
+        &[
+          (xproto::GC_FOREGROUND, LABEL_TEXT),
+          (xproto::GC_BACKGROUND, HIGHLIGHT),
+          (xproto::GC_FONT, font),
+        ],
+      ),
+      "CreateGC",
+      format_args!("{:#x}", text),
+    );
+
+    let mut shown = Labels {
+      conn,
+      windows: vec![],
+      pixmaps: vec![],
+    };
+    for (center, label) in labels {
+      let size = ScreenSize::new(char_width * label.len() as i32 + 2 * LABEL_PADDING, height);
+      let pixmap = conn.generate_id();
+      trace::sent(
+        xproto::create_pixmap(
+          conn,
+          depth,
+          pixmap,
+          root,
+          size.width as u16,
+          size.height as u16,
+        ),
+        "CreatePixmap",
+        format_args!("{:#x} {}", pixmap, size),
+      );
+      shown.pixmaps.push(pixmap);
+      trace::sent(
+        xproto::poly_fill_rectangle(
+          conn,
+          pixmap,
+          fill,
+          &[xproto::Rectangle::new(
+            0,
+            0,
+            size.width as u16,
+            size.height as u16,
+          )],
+        ),
+        "PolyFillRectangle",
+        format_args!("{:#x}", pixmap),
+      );
+      trace::sent(
+        xproto::image_text_8(
+          conn,
+          pixmap,
+          text,
+          LABEL_PADDING as i16,
+          (LABEL_PADDING + ascent) as i16,
+          label,
+        ),
+        "ImageText8",
+        format_args!("{:#x} {}", pixmap, label),
+      );
+
+      let window = create_window_with(conn, root, (xproto::CW_BACK_PIXMAP, pixmap));
+      shown.windows.push(window);
+      show_window(
+        conn,
+        window,
+        ScreenRect::new(*center - size.to_vector() / 2, size),
+      );
+    }
+
+    for gc in &[fill, text] {
+      trace::sent(
+        xproto::free_gc(conn, *gc),
+        "FreeGC",
+        format_args!("{:#x}", gc),
+      );
+    }
+    trace::sent(
+      xproto::close_font(conn, font),
+      "CloseFont",
+      format_args!("{:#x}", font),
+    );
+    conn.flush();
+    Ok(shown)
+  }
+}
+
+impl Drop for Labels<'_> {
+  fn drop(&mut self) {
+    for window in &self.windows {
+      destroy_window(self.conn, *window);
+    }
+    for pixmap in &self.pixmaps {
+      trace::sent(
+        xproto::free_pixmap(self.conn, *pixmap),
+        "FreePixmap",
+        format_args!("{:#x}", pixmap),
+      );
+    }
+    self.conn.flush();
+  }
+}
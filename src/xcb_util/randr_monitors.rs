@@ -0,0 +1,161 @@
+//! `RRGetMonitors`, from RandR 1.5, which the xcb crate doesn't bind yet.
+//!
+//! libxcb-randr has had the request since 1.11, so only the declarations are
+//! needed here. Cookies fit in with the rest of xcb's, so they can be traced
+//! and waited on with `reply!` like any other.
+
+#![allow(non_camel_case_types)]
+
+use std::{
+  os::raw::{
+    c_int,
+    c_uint,
+  },
+  ptr,
+};
+
+use xcb::{
+  base as xbase,
+  ffi::base::{
+    xcb_connection_t,
+    xcb_generic_error_t,
+  },
+  xproto,
+};
+
+use crate::xcb_util::geometry::*;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct xcb_randr_get_monitors_cookie_t {
+  sequence: c_uint,
+}
+
+impl xbase::CookieSeq for xcb_randr_get_monitors_cookie_t {
+  fn sequence(&self) -> c_uint { self.sequence }
+}
+
+// These match libxcb's layout, so not every field is read.
+#[repr(C)]
+#[allow(dead_code)]
+struct xcb_randr_get_monitors_reply_t {
+  response_type: u8,
+  pad0: u8,
+  sequence: u16,
+  length: u32,
+  timestamp: xproto::Timestamp,
+  n_monitors: u32,
+  n_outputs: u32,
+  pad1: [u8; 12],
+}
+
+#[repr(C)]
+#[allow(dead_code)]
+struct xcb_randr_monitor_info_t {
+  name: xproto::Atom,
+  primary: u8,
+  automatic: u8,
+  n_output: u16,
+  x: i16,
+  y: i16,
+  width: u16,
+  height: u16,
+  width_in_millimeters: u32,
+  height_in_millimeters: u32,
+}
+
+#[repr(C)]
+struct xcb_randr_monitor_info_iterator_t {
+  data: *mut xcb_randr_monitor_info_t,
+  rem: c_int,
+  index: c_int,
+}
+
+extern "C" {
+  fn xcb_randr_get_monitors(
+    c: *mut xcb_connection_t,
+    window: xproto::Window,
+    get_active: u8,
+  ) -> xcb_randr_get_monitors_cookie_t;
+
+  fn xcb_randr_get_monitors_reply(
+    c: *mut xcb_connection_t,
+    cookie: xcb_randr_get_monitors_cookie_t,
+    e: *mut *mut xcb_generic_error_t,
+  ) -> *mut xcb_randr_get_monitors_reply_t;
+
+  fn xcb_randr_get_monitors_monitors_iterator(
+    r: *const xcb_randr_get_monitors_reply_t,
+  ) -> xcb_randr_monitor_info_iterator_t;
+
+  fn xcb_randr_monitor_info_next(i: *mut xcb_randr_monitor_info_iterator_t);
+}
+
+/// A RandR monitor: either one output, several outputs that make up one
+/// display (as with tiled 5K and 8K panels), or a region set up with
+/// `xrandr --setmonitor`.
+pub struct Monitor {
+  pub name: xproto::Atom,
+  pub primary: bool,
+  pub frame: ScreenRect,
+}
+
+pub type GetMonitorsCookie<'a> = xbase::Cookie<'a, xcb_randr_get_monitors_cookie_t>;
+
+/// Lists the monitors on `window`'s screen; with `get_active`, only those
+/// that are currently showing something.
+pub fn get_monitors<'a>(
+  conn: &'a xbase::Connection,
+  window: xproto::Window,
+  get_active: bool,
+) -> GetMonitorsCookie<'a> {
+  let cookie = unsafe { xcb_randr_get_monitors(conn.get_raw_conn(), window, get_active as u8) };
+  xbase::Cookie {
+    cookie,
+    conn,
+    checked: true,
+  }
+}
+
+pub trait GetMonitorsCookieExt {
+  fn get_reply(self) -> Result<Vec<Monitor>, xbase::GenericError>;
+}
+
+impl<'a> GetMonitorsCookieExt for GetMonitorsCookie<'a> {
+  fn get_reply(self) -> Result<Vec<Monitor>, xbase::GenericError> {
+    let raw_conn = self.conn.get_raw_conn();
+    let cookie = self.cookie;
+    // The reply is collected here, so the cookie mustn't discard it on drop.
+    std::mem::forget(self);
+
+    unsafe {
+      let mut err: *mut xcb_generic_error_t = ptr::null_mut();
+      let reply = xcb_randr_get_monitors_reply(raw_conn, cookie, &mut err);
+      if !err.is_null() {
+        return Err(xbase::GenericError { ptr: err });
+      }
+      if reply.is_null() {
+        // The connection has failed; later requests will say so.
+        return Ok(vec![]);
+      }
+      // Frees the reply once the monitors have been copied out.
+      let reply = xbase::Reply { ptr: reply };
+
+      let mut monitors = vec![];
+      let mut iter = xcb_randr_get_monitors_monitors_iterator(reply.ptr);
+      while iter.rem > 0 {
+        let info = &*iter.data;
+        monitors.push(Monitor {
+          name: info.name,
+          primary: info.primary != 0,
+          frame: ScreenRect::new(
+            ScreenPoint::new(info.x as i32, info.y as i32),
+            ScreenSize::new(info.width as i32, info.height as i32),
+          ),
+        });
+        xcb_randr_monitor_info_next(&mut iter);
+      }
+      Ok(monitors)
+    }
+  }
+}
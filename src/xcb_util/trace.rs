@@ -0,0 +1,80 @@
+//! Logging of X requests and replies, for `--trace-x11`.
+//!
+//! Requests are logged as they're sent, and replies as they're received, both
+//! with the request's sequence number so the two can be matched up when
+//! requests are pipelined. Request names are also remembered until the reply
+//! arrives, so that X errors can say which request failed.
+
+use std::{
+  cell::RefCell,
+  collections::HashMap,
+  fmt,
+};
+
+use log::trace;
+use xcb::base::{
+  Cookie,
+  CookieSeq,
+  GenericError,
+};
+
+use crate::error::TileError;
+
+thread_local! {
+  static PENDING: RefCell<HashMap<u32, &'static str>> = RefCell::new(HashMap::new());
+}
+
+/// The log target used for every request and reply.
+pub const TARGET: &str = "xtrace";
+
+/// Logs a request that has just been sent, returning its cookie.
+///
+/// Only checked requests, and requests with replies, are remembered for
+/// `received`; nothing ever arrives for the others, so they'd never be
+/// forgotten.
+pub fn sent<'a, T: Copy + CookieSeq>(
+  cookie: Cookie<'a, T>,
+  request: &'static str,
+  args: fmt::Arguments,
+) -> Cookie<'a, T> {
+  let seq = cookie.cookie.sequence();
+  trace!(target: TARGET, seq = seq; "-> {} {}", request, args);
+  if cookie.checked {
+    PENDING.with(|pending| pending.borrow_mut().insert(seq, request));
+  }
+  cookie
+}
+
+/// Logs the outcome of a request, converting any error into a `TileError`.
+pub fn received<R>(seq: u32, result: Result<R, GenericError>) -> Result<R, TileError> {
+  let request = PENDING
+    .with(|pending| pending.borrow_mut().remove(&seq))
+    .unwrap_or("unknown request");
+  match result {
+    Ok(reply) => {
+      trace!(target: TARGET, seq = seq; "<- reply");
+      Ok(reply)
+    }
+    Err(e) => {
+      trace!(target: TARGET, seq = seq, error = e.error_code(); "<- error");
+      Err(TileError::XError {
+        request,
+        code: e.error_code(),
+      })
+    }
+  }
+}
+
+/// Waits for the reply to a cookie, logging it.
+///
+/// This is a macro rather than a function because each cookie type has its own
+/// `get_reply`.
+macro_rules! reply {
+  ($cookie:expr) => {{
+    let cookie = $cookie;
+    let seq = xcb::base::CookieSeq::sequence(&cookie.cookie);
+    $crate::xcb_util::trace::received(seq, cookie.get_reply())
+  }};
+}
+
+pub(crate) use reply;
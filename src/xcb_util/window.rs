@@ -1,17 +1,33 @@
+use std::io;
+
 use anyhow::{
   anyhow,
   Error,
 };
 use bitflags::bitflags;
+use log::debug;
+use serde::{
+  Deserialize,
+  Serialize,
+};
 use xcb::{
-  base as xbase,
-  randr as xrandr,
+  base::{
+    self as xbase,
+    CookieSeq,
+  },
   xproto,
 };
 
-use crate::xcb_util::{
-  connection::ConnectionExt,
-  geometry::*,
+use crate::{
+  error::TileError,
+  xcb_util::{
+    connection::ConnectionExt,
+    geometry::*,
+    trace::{
+      self,
+      reply,
+    },
+  },
 };
 
 bitflags! {
@@ -30,6 +46,79 @@ bitflags! {
     }
 }
 
+/// The `_NET_WM_DESKTOP` of windows that are on every desktop.
+pub const ALL_DESKTOPS: u32 = 0xFFFF_FFFF;
+
+/// The ICCCM `WM_STATE` of a minimised window.
+const ICONIC_STATE: u32 = 3;
+
+/// The parts of a window's `WM_NORMAL_HINTS` that constrain its size.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SizeHints {
+  pub min_size: Option<ScreenSize>,
+  pub max_size: Option<ScreenSize>,
+  pub base_size: Option<ScreenSize>,
+  pub resize_inc: Option<ScreenSize>,
+}
+
+impl SizeHints {
+  pub fn from_wm_normal_hints(value: &[u32]) -> SizeHints {
+    let flags = match value.first() {
+      Some(flags) => WMSizeHintsFlag::from_bits_truncate(*flags),
+      None => return SizeHints::default(),
+    };
+
+    // Pre-ICCCM clients may send the shorter 15-field version, without a base
+    // size or gravity.
+    let size = |flag: WMSizeHintsFlag, i: usize| {
+      if flags.contains(flag) && value.len() > i + 1 {
+        Some(ScreenSize::new(value[i] as i32, value[i + 1] as i32))
+      } else {
+        None
+      }
+    };
+
+    SizeHints {
+      min_size: size(WMSizeHintsFlag::P_MIN_SIZE, 5),
+      max_size: size(WMSizeHintsFlag::P_MAX_SIZE, 7),
+      base_size: size(WMSizeHintsFlag::BASE_SIZE, 15),
+      resize_inc: size(WMSizeHintsFlag::P_RESIZE_INC, 9),
+    }
+  }
+
+  /// The size a window following these hints would actually take if asked to
+  /// be `size`: clamped to the min and max sizes, then rounded down to a whole
+  /// number of resize increments above the base size.
+  pub fn constrain(&self, size: ScreenSize) -> ScreenSize {
+    let mut size = size;
+    if let Some(min) = self.min_size {
+      size = size.max(min);
+    }
+    if let Some(max) = self.max_size {
+      size = size.min(max);
+    }
+    if let Some(inc) = self.resize_inc {
+      // ICCCM says the base size is the minimum size if there isn't one.
+      let base = self
+        .base_size
+        .or(self.min_size)
+        .unwrap_or_else(ScreenSize::zero);
+      let round = |size: i32, base: i32, inc: i32| {
+        if inc > 0 && size > base {
+          base + (size - base) / inc * inc
+        } else {
+          size
+        }
+      };
+      size = ScreenSize::new(
+        round(size.width, base.width, inc.width),
+        round(size.height, base.height, inc.height),
+      );
+    }
+    size
+  }
+}
+
 pub trait WindowExt {
   fn get_property<T: Clone>(
     &self,
@@ -39,6 +128,7 @@ pub trait WindowExt {
     count: usize,
   ) -> Result<Vec<T>, Error>;
 
+  #[allow(dead_code)]
   fn change_property<T>(
     &self,
     connection: &xbase::Connection,
@@ -57,24 +147,81 @@ pub trait WindowExt {
     event: &xcb::Event<T>,
   ) -> Result<(), Error>;
 
-  fn get_screen_resources_current(
+  /// The window's rect in the coordinates of `root`.
+  fn get_frame(
     &self,
     connection: &xbase::Connection,
-  ) -> Result<xrandr::GetScreenResourcesCurrentReply, Error>;
-
-  fn get_active_window(&self, connection: &xbase::Connection) -> Result<xproto::Window, Error>;
-
-  fn get_geometry(&self, connection: &xbase::Connection)
-    -> Result<xproto::GetGeometryReply, Error>;
+    root: xproto::Window,
+  ) -> Result<ScreenRect, Error>;
 
   fn supports(&self, connection: &xbase::Connection, msg: &str) -> Result<bool, Error>;
 
+  /// Sends an EWMH client message about `target` to the WM, failing if the WM
+  /// doesn't support it.
+  fn client_message(
+    &self,
+    connection: &xbase::Connection,
+    target: xproto::Window,
+    message: &'static str,
+    data: [u32; 5],
+  ) -> Result<(), Error>;
+
   fn move_resize(
     &self,
     connection: &xbase::Connection,
     target: xproto::Window,
     new_rect: ScreenRect,
   ) -> Result<(), Error>;
+
+  fn set_current_desktop(&self, connection: &xbase::Connection, desktop: u32) -> Result<(), Error>;
+
+  /// Moves `target` to `desktop`, which may be `ALL_DESKTOPS`.
+  fn set_desktop(
+    &self,
+    connection: &xbase::Connection,
+    target: xproto::Window,
+    desktop: u32,
+  ) -> Result<(), Error>;
+
+  /// Asks the WM to focus and raise `target`, switching desktops or
+  /// unminimising it as needed.
+  fn activate(&self, connection: &xbase::Connection, target: xproto::Window) -> Result<(), Error>;
+
+  /// Moves `target` directly above or below `sibling` (one of
+  /// `xproto::STACK_MODE_*`), or to the top or bottom of its layer if there's
+  /// no sibling.
+  fn restack(
+    &self,
+    connection: &xbase::Connection,
+    target: xproto::Window,
+    sibling: Option<xproto::Window>,
+    mode: u32,
+  ) -> Result<(), Error>;
+
+  /// Adds or removes one of `target`'s `_NET_WM_STATE` atoms, such as
+  /// `_NET_WM_STATE_ABOVE`.
+  fn set_state(
+    &self,
+    connection: &xbase::Connection,
+    target: xproto::Window,
+    state: &str,
+    enabled: bool,
+  ) -> Result<(), Error>;
+
+  /// Asks the WM to minimise `target`.
+  fn iconify(&self, connection: &xbase::Connection, target: xproto::Window) -> Result<(), Error>;
+
+  /// Turns the WM's "show desktop" mode, which hides every window, on or off.
+  fn set_showing_desktop(&self, connection: &xbase::Connection, showing: bool)
+    -> Result<(), Error>;
+
+  /// Asks `target` to close, through the WM if it supports
+  /// `_NET_CLOSE_WINDOW`, or directly with `WM_DELETE_WINDOW` otherwise.
+  fn close(&self, connection: &xbase::Connection, target: xproto::Window) -> Result<(), Error>;
+
+  /// Kills the client that owns this window: its process, if it's running on
+  /// this machine, or else its connection to the X server.
+  fn kill(&self, connection: &xbase::Connection) -> Result<(), Error>;
 }
 
 impl WindowExt for xproto::Window {
@@ -86,7 +233,12 @@ impl WindowExt for xproto::Window {
     count: usize,
   ) -> Result<Vec<T>, Error> {
     let atom = connection.get_atom(property)?;
-    Ok(
+    // Atoms are interned only if they exist, so a property no client has ever
+    // set (like `_NET_SUPPORTED` without an EWMH WM) comes back as none.
+    if atom == xproto::ATOM_NONE {
+      return Ok(vec![]);
+    }
+    let cookie = trace::sent(
       xproto::get_property(
         connection,
         false,
@@ -95,11 +247,11 @@ impl WindowExt for xproto::Window {
         type_,
         0,
         (count * std::mem::size_of::<T>()) as u32,
-      )
-      .get_reply()?
-      .value::<T>()
-      .to_vec(),
-    )
+      ),
+      "GetProperty",
+      format_args!("{:#x} {}", self, property),
+    );
+    Ok(reply!(cookie)?.value::<T>().to_vec())
   }
 
   fn change_property<T>(
@@ -112,7 +264,11 @@ impl WindowExt for xproto::Window {
     data: &[T],
   ) -> Result<(), Error> {
     let atom = connection.get_atom(property)?;
-    xproto::change_property(connection, mode, *self, atom, type_, format, data);
+    trace::sent(
+      xproto::change_property(connection, mode, *self, atom, type_, format, data),
+      "ChangeProperty",
+      format_args!("{:#x} {}", self, property),
+    );
 
     Ok(())
   }
@@ -124,33 +280,36 @@ impl WindowExt for xproto::Window {
     mask: u32,
     event: &xcb::Event<T>,
   ) -> Result<(), Error> {
-    xproto::send_event(&connection, propagate, *self, mask, event)
-      .request_check()
-      .map_err(|e| anyhow!("{}", e))
+    let cookie = trace::sent(
+      xproto::send_event_checked(connection, propagate, *self, mask, event),
+      "SendEvent",
+      format_args!("{:#x}", self),
+    );
+    let seq = cookie.cookie.sequence();
+    Ok(trace::received(seq, cookie.request_check())?)
   }
 
-  fn get_screen_resources_current(
+  fn get_frame(
     &self,
     connection: &xbase::Connection,
-  ) -> Result<xrandr::GetScreenResourcesCurrentReply, Error> {
-    Ok(
-      xrandr::get_screen_resources_current(&connection, *self)
-        .get_reply()
-        .map_err(|e| anyhow!("Couldn't get screen resources: {}", e))?,
-    )
-  }
-
-  fn get_active_window(&self, connection: &xbase::Connection) -> Result<xproto::Window, Error> {
-    Ok(self.get_property(connection, "_NET_ACTIVE_WINDOW", xproto::ATOM_WINDOW, 1)?[0])
-  }
+    root: xproto::Window,
+  ) -> Result<ScreenRect, Error> {
+    let geometry = trace::sent(
+      xproto::get_geometry(connection, *self),
+      "GetGeometry",
+      format_args!("{:#x}", self),
+    );
+    let translated = trace::sent(
+      xproto::translate_coordinates(connection, *self, root, 0, 0),
+      "TranslateCoordinates",
+      format_args!("{:#x} {:#x}", self, root),
+    );
 
-  fn get_geometry(
-    &self,
-    connection: &xbase::Connection,
-  ) -> Result<xproto::GetGeometryReply, Error> {
-    xproto::get_geometry(connection, *self)
-      .get_reply()
-      .map_err(|e| anyhow!("Couldn't get window geometry: {}", e))
+    let mut frame = reply!(geometry)?.as_rect();
+    let translated = reply!(translated)?;
+    frame.origin.x = translated.dst_x() as i32;
+    frame.origin.y = translated.dst_y() as i32;
+    Ok(frame)
   }
 
   fn supports(&self, connection: &xbase::Connection, msg: &str) -> Result<bool, Error> {
@@ -160,47 +319,263 @@ impl WindowExt for xproto::Window {
     Ok(list.contains(&atom))
   }
 
-  fn move_resize(
+  fn client_message(
     &self,
     connection: &xbase::Connection,
     target: xproto::Window,
-    new_rect: ScreenRect,
+    message: &'static str,
+    data: [u32; 5],
   ) -> Result<(), Error> {
-    if !self.supports(connection, "_NET_MOVERESIZE_WINDOW")? {
-      return Err(anyhow!("WM doesn't support _NET_MOVERESIZE_WINDOW"));
+    if !self.supports(connection, message)? {
+      return Err(TileError::WmUnsupported { atom: message }.into());
     }
+    send_client_message(*self, connection, target, message, data)
+  }
 
+  fn move_resize(
+    &self,
+    connection: &xbase::Connection,
+    target: xproto::Window,
+    new_rect: ScreenRect,
+  ) -> Result<(), Error> {
     // TODO: KWin's built-in window tiling seems to prevent this from working. Find
     // out why. use xprop to examine window properties
 
-    println!("running move_resize");
+    debug!(window = target, frame:% = new_rect; "Sending _NET_MOVERESIZE_WINDOW");
 
     // bits 8-11 are presence bits for x/y/w/h
     // bits 12-15 indicate request source (bit 13 indicates a user-interactive
     // source)
     let flags = xproto::GRAVITY_STATIC | 1 << 8 | 1 << 9 | 1 << 10 | 1 << 11 | 1 << 12;
 
-    let ev = xcb::ClientMessageEvent::new(
-      32,
+    self.client_message(
+      connection,
       target,
-      connection.get_atom("_NET_MOVERESIZE_WINDOW")?,
-      xproto::ClientMessageData::from_data32([
+      "_NET_MOVERESIZE_WINDOW",
+      [
         flags,
         new_rect.origin.x as u32,
         new_rect.origin.y as u32,
         new_rect.size.width as u32,
         new_rect.size.height as u32,
-      ]),
-    );
+      ],
+    )
+  }
+
+  fn set_current_desktop(&self, connection: &xbase::Connection, desktop: u32) -> Result<(), Error> {
+    debug!(desktop = desktop; "Sending _NET_CURRENT_DESKTOP");
+    self.client_message(
+      connection,
+      *self,
+      "_NET_CURRENT_DESKTOP",
+      [desktop, xbase::CURRENT_TIME, 0, 0, 0],
+    )
+  }
+
+  fn set_desktop(
+    &self,
+    connection: &xbase::Connection,
+    target: xproto::Window,
+    desktop: u32,
+  ) -> Result<(), Error> {
+    debug!(window = target, desktop = desktop; "Sending _NET_WM_DESKTOP");
+    // The second field is the source indication; 2 is a pager, which WMs are
+    // least likely to second-guess.
+    self.client_message(connection, target, "_NET_WM_DESKTOP", [desktop, 2, 0, 0, 0])
+  }
+
+  fn activate(&self, connection: &xbase::Connection, target: xproto::Window) -> Result<(), Error> {
+    debug!(window = target; "Sending _NET_ACTIVE_WINDOW");
+    self.client_message(
+      connection,
+      target,
+      "_NET_ACTIVE_WINDOW",
+      [2, xbase::CURRENT_TIME, 0, 0, 0],
+    )
+  }
+
+  fn restack(
+    &self,
+    connection: &xbase::Connection,
+    target: xproto::Window,
+    sibling: Option<xproto::Window>,
+    mode: u32,
+  ) -> Result<(), Error> {
+    debug!(window = target, sibling = sibling, mode = mode; "Sending _NET_RESTACK_WINDOW");
+    self.client_message(
+      connection,
+      target,
+      "_NET_RESTACK_WINDOW",
+      [2, sibling.unwrap_or(xbase::NONE), mode, 0, 0],
+    )
+  }
+
+  fn set_state(
+    &self,
+    connection: &xbase::Connection,
+    target: xproto::Window,
+    state: &str,
+    enabled: bool,
+  ) -> Result<(), Error> {
+    debug!(window = target, state = state, enabled = enabled; "Sending _NET_WM_STATE");
+    // The first field is 0 to remove the state, or 1 to add it.
+    self.client_message(
+      connection,
+      target,
+      "_NET_WM_STATE",
+      [enabled as u32, connection.get_atom(state)?, 0, 2, 0],
+    )
+  }
+
+  fn iconify(&self, connection: &xbase::Connection, target: xproto::Window) -> Result<(), Error> {
+    debug!(window = target; "Sending WM_CHANGE_STATE");
+    // This comes from ICCCM rather than EWMH, so it isn't in _NET_SUPPORTED;
+    // every WM is expected to handle it.
+    send_client_message(
+      *self,
+      connection,
+      target,
+      "WM_CHANGE_STATE",
+      [ICONIC_STATE, 0, 0, 0, 0],
+    )
+  }
 
-    xproto::send_event(
+  fn set_showing_desktop(
+    &self,
+    connection: &xbase::Connection,
+    showing: bool,
+  ) -> Result<(), Error> {
+    debug!(showing = showing; "Sending _NET_SHOWING_DESKTOP");
+    self.client_message(
       connection,
-      true,
       *self,
-      xproto::EVENT_MASK_SUBSTRUCTURE_NOTIFY | xproto::EVENT_MASK_SUBSTRUCTURE_REDIRECT,
-      &ev,
+      "_NET_SHOWING_DESKTOP",
+      [showing as u32, 0, 0, 0, 0],
     )
-    .request_check()
-    .map_err(|_| anyhow!("???"))
+  }
+
+  fn close(&self, connection: &xbase::Connection, target: xproto::Window) -> Result<(), Error> {
+    if self.supports(connection, "_NET_CLOSE_WINDOW")? {
+      debug!(window = target; "Sending _NET_CLOSE_WINDOW");
+      return self.client_message(
+        connection,
+        target,
+        "_NET_CLOSE_WINDOW",
+        [xbase::CURRENT_TIME, 2, 0, 0, 0],
+      );
+    }
+
+    let delete_window = connection.get_atom("WM_DELETE_WINDOW")?;
+    let protocols: Vec<xproto::Atom> =
+      target.get_property(connection, "WM_PROTOCOLS", xproto::ATOM_ATOM, 32)?;
+    if !protocols.contains(&delete_window) {
+      return Err(anyhow!(
+        "{:#x} doesn't support being asked to close; use --force to kill it",
+        target
+      ));
+    }
+
+    debug!(window = target; "Sending WM_DELETE_WINDOW");
+    let ev = xcb::ClientMessageEvent::new(
+      32,
+      target,
+      connection.get_atom("WM_PROTOCOLS")?,
+      xproto::ClientMessageData::from_data32([delete_window, xbase::CURRENT_TIME, 0, 0, 0]),
+    );
+    target.send_event(connection, false, xproto::EVENT_MASK_NO_EVENT, &ev)
+  }
+
+  fn kill(&self, connection: &xbase::Connection) -> Result<(), Error> {
+    let pid = self
+      .get_property::<u32>(connection, "_NET_WM_PID", xproto::ATOM_CARDINAL, 1)?
+      .first()
+      .copied()
+      .and_then(process_id);
+    let machine =
+      self.get_property::<u8>(connection, "WM_CLIENT_MACHINE", xproto::ATOM_STRING, 256)?;
+    let local = hostname().is_some_and(|host| host.as_bytes() == machine.as_slice());
+
+    match pid {
+      // A PID from another machine would kill the wrong process.
+      Some(pid) if local => {
+        debug!(window = *self, pid = pid; "Killing process");
+        if unsafe { libc::kill(pid, libc::SIGKILL) } != 0 {
+          return Err(anyhow!(
+            "Couldn't kill process {}: {}",
+            pid,
+            io::Error::last_os_error()
+          ));
+        }
+        Ok(())
+      }
+      _ => {
+        debug!(window = *self; "Sending KillClient");
+        let cookie = trace::sent(
+          xproto::kill_client_checked(connection, *self),
+          "KillClient",
+          format_args!("{:#x}", self),
+        );
+        let seq = cookie.cookie.sequence();
+        Ok(trace::received(seq, cookie.request_check())?)
+      }
+    }
+  }
+}
+
+/// `_NET_WM_PID` as a single process to kill, or None if it isn't one. `kill`
+/// takes 0 and anything that casts to a negative number to mean a whole
+/// process group, which could include x11-tile itself.
+fn process_id(pid: u32) -> Option<libc::pid_t> {
+  if pid > 0 && pid <= libc::pid_t::MAX as u32 {
+    Some(pid as libc::pid_t)
+  } else {
+    None
+  }
+}
+
+/// This machine's name, to compare against `WM_CLIENT_MACHINE`.
+fn hostname() -> Option<String> {
+  let mut buf = [0u8; 256];
+  if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } != 0 {
+    return None;
+  }
+  let len = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+  String::from_utf8(buf[..len].to_vec()).ok()
+}
+
+/// Sends a client message about `target` to the WM through `root`.
+fn send_client_message(
+  root: xproto::Window,
+  connection: &xbase::Connection,
+  target: xproto::Window,
+  message: &str,
+  data: [u32; 5],
+) -> Result<(), Error> {
+  let ev = xcb::ClientMessageEvent::new(
+    32,
+    target,
+    connection.get_atom(message)?,
+    xproto::ClientMessageData::from_data32(data),
+  );
+
+  root.send_event(
+    connection,
+    true,
+    xproto::EVENT_MASK_SUBSTRUCTURE_NOTIFY | xproto::EVENT_MASK_SUBSTRUCTURE_REDIRECT,
+    &ev,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn only_single_processes_are_killed() {
+    assert_eq!(process_id(1234), Some(1234));
+    assert_eq!(process_id(i32::MAX as u32), Some(i32::MAX));
+    assert_eq!(process_id(0), None);
+    assert_eq!(process_id(i32::MAX as u32 + 1), None);
+    assert_eq!(process_id(u32::MAX), None);
   }
 }
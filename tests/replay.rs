@@ -0,0 +1,59 @@
+//! Replays commands against the snapshots in `tests/fixtures`, and compares
+//! the requests they print with the expected output stored next to each
+//! snapshot.
+//!
+//! An `.expected` file lists each command after `$ `, followed by the lines
+//! it prints and its exit code if that isn't 0. Run with `UPDATE_EXPECTED=1`
+//! to rewrite the files from the current output.
+
+use std::{
+  env,
+  fs,
+  path::Path,
+  process::Command,
+};
+
+fn replay(snapshot: &Path, args: &str) -> String {
+  let output = Command::new(env!("CARGO_BIN_EXE_x11-tile"))
+    // There's no x11-tile directory in there, so the user's config is ignored.
+    .env("XDG_CONFIG_HOME", snapshot.parent().unwrap())
+    .arg("--replay")
+    .arg(snapshot)
+    .args(args.split_whitespace())
+    .output()
+    .unwrap();
+  let mut printed = String::from_utf8(output.stdout).unwrap();
+  match output.status.code() {
+    Some(0) => {}
+    Some(code) => printed += &format!("exit {}\n", code),
+    None => panic!("`{}` was killed by a signal", args),
+  }
+  printed
+}
+
+fn check(name: &str) {
+  let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+  let snapshot = fixtures.join(name).with_extension("json");
+  let expected_path = fixtures.join(name).with_extension("expected");
+  let expected = fs::read_to_string(&expected_path).unwrap();
+
+  let actual = expected
+    .lines()
+    .filter_map(|line| line.strip_prefix("$ "))
+    .map(|args| format!("$ {}\n{}", args, replay(&snapshot, args)))
+    .collect::<String>();
+
+  if env::var_os("UPDATE_EXPECTED").is_some() {
+    fs::write(&expected_path, actual).unwrap();
+  } else {
+    assert_eq!(
+      actual,
+      expected,
+      "{} differs; rerun with UPDATE_EXPECTED=1 to accept the new output",
+      expected_path.display()
+    );
+  }
+}
+
+#[test]
+fn l_shaped() { check("l-shaped"); }